@@ -11,14 +11,24 @@ echo "Running in $dir"
 cp -r test "$dir"
 cd "$dir"
 
-# an invalid rust version does nothing
-travis-cargo --only xx$TRAVIS_RUST_VERSION build
+# an invalid rust version is an error, and does nothing
+! travis-cargo --only xx$TRAVIS_RUST_VERSION build
 test ! -d target
 
 # a skipped version does nothing
 travis-cargo --skip $TRAVIS_RUST_VERSION build
 test ! -d target
 
+# versions are also compared against the toolchain from `rustc -vV`
+travis-cargo --only '<1.0' build
+test ! -d target
+
+# a negated version does nothing, and all of the `--only` versions have to match
+travis-cargo --only "!$TRAVIS_RUST_VERSION" build
+test ! -d target
+travis-cargo --only "$TRAVIS_RUST_VERSION" --only '<1.0' build
+test ! -d target
+
 # noisy builds by default
 travis-cargo build | grep Running
 cargo clean
@@ -52,14 +62,24 @@ travis-cargo coverage -m target/coverage --verify -- --features 'remove-failing'
 for pattern in "$dir/tests/foo.rs" "$dir/src/lib.rs"; do
     grep "$pattern" target/coverage/kcov-merged/index.json
 done
-rm -rf target/coverage kcov
+rm -rf target/coverage
 
-travis-cargo coverage -m target/coverage -- --features 'remove-failing'
+travis-cargo coverage -m target/coverage --format lcov -- --features 'remove-failing'
 # the two source file names should appear somewhere in the coverage
-# output
+# output, and in the converted report
 for pattern in "$dir/tests/foo.rs" "$dir/src/lib.rs"; do
     grep "$pattern" target/coverage/kcov-merged/index.json
+    grep "SF:.*$pattern" target/coverage/lcov.info
 done
 
-rm -rf kcov
+# a minimum that isn't met fails with its own exit status, after listing the
+# offending files
+cp Cargo.toml Cargo.toml.orig
+printf '[package.metadata.travis-cargo.coverage.files]\n"src/uncovered.rs" = 1\n' >> Cargo.toml
+status=0
+travis-cargo coverage -m target/coverage -- --features 'remove-failing' > coverage.log || status=$?
+test "$status" -eq 6
+grep src/uncovered.rs coverage.log
+mv Cargo.toml.orig Cargo.toml
+
 travis-cargo coveralls -- --features 'remove-failing'
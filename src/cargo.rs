@@ -1,20 +1,30 @@
 use std::env;
 use std::process::Command;
 
+use Toolchain;
+use error::Result;
 use utils;
 
-pub fn cargo_feature(version: &str, quiet: bool, command: &str, args: &[String]) {
-    cargo_raw(true, version, quiet, command, args);
+pub fn cargo_feature(toolchain: &Toolchain,
+                     quiet: bool,
+                     command: &str,
+                     args: &[String])
+                     -> Result<()> {
+    cargo_raw(true, toolchain, quiet, command, args)
 }
 
-pub fn cargo_no_feature(version: &str, quiet: bool, command: &str, args: &[String]) {
-    cargo_raw(false, version, quiet, command, args);
+pub fn cargo_no_feature(toolchain: &Toolchain,
+                        quiet: bool,
+                        command: &str,
+                        args: &[String])
+                        -> Result<()> {
+    cargo_raw(false, toolchain, quiet, command, args)
 }
 
-pub fn add_features(cargo_args: &mut Vec<String>, version: &str) {
+pub fn add_features(cargo_args: &mut Vec<String>, toolchain: &Toolchain) {
     let nightly_feature = env::var("TRAVIS_CARGO_NIGHTLY_FEATURE").unwrap_or("unstable".to_owned());
 
-    if version == "nightly" && nightly_feature != "" {
+    if toolchain.channel.is_nightly() && nightly_feature != "" {
         // Only touch feature arguments when we are actually going to add something non-trivial,
         // avoids problems like that in issue #14 (can't use -p ... on nightly even with an empty
         // nightly feature).
@@ -36,21 +46,26 @@ pub fn add_features(cargo_args: &mut Vec<String>, version: &str) {
     }
 }
 
-fn cargo_raw(feature: bool, version: &str, quiet: bool, command: &str, args: &[String]) {
+fn cargo_raw(feature: bool,
+             toolchain: &Toolchain,
+             quiet: bool,
+             command: &str,
+             args: &[String])
+             -> Result<()> {
     let mut cargo_args: Vec<String> = args.iter().cloned().collect();
 
-    if command == "bench" && version != "nightly" {
+    if command == "bench" && !toolchain.channel.is_nightly() {
         println!("skipping `cargo bench` on non-nightly version");
-        return;
+        return Ok(());
     }
 
     if feature {
-        add_features(&mut cargo_args, version);
+        add_features(&mut cargo_args, toolchain);
     }
 
     if !quiet && !cargo_args.contains(&"--verbose".to_owned()) && !args.contains(&"-v".to_owned()) {
         cargo_args.push("--verbose".into());
     }
 
-    utils::run(Command::new("cargo").arg(command).args(&cargo_args));
+    utils::run(Command::new("cargo").arg(command).args(&cargo_args))
 }
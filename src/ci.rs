@@ -0,0 +1,660 @@
+use std::env;
+
+use utils::git;
+
+/// The facts about the current build that travis-cargo needs from the CI service running it.
+///
+/// Every method returns `None` when the service doesn't provide that fact for this build, e.g.
+/// `pull_request` outside of a pull request build.
+pub trait CiEnvironment {
+    /// The name of the service, as used by coverage services such as coveralls.io.
+    fn name(&self) -> &'static str;
+
+    /// The branch being built. For pull requests, this is the branch being merged.
+    fn branch(&self) -> Option<String>;
+
+    /// The repository, as `owner/name`.
+    fn repo_slug(&self) -> Option<String>;
+
+    /// The number of the pull request being built.
+    fn pull_request(&self) -> Option<String>;
+
+    fn job_id(&self) -> Option<String>;
+
+    fn build_number(&self) -> Option<String>;
+
+    /// The web page of the build.
+    fn build_url(&self) -> Option<String>;
+
+    /// The SHA of the commit being built.
+    fn commit(&self) -> Option<String>;
+
+    /// The tag being built.
+    fn tag(&self) -> Option<String>;
+
+    /// Whether secret variables (such as `GH_TOKEN`) are available to this build. Services
+    /// generally withhold them from pull requests, which may come from untrusted forks.
+    fn is_secure_env_available(&self) -> bool;
+
+    /// The Rust version requested by the build configuration, if the service has a notion of
+    /// one.
+    fn rust_version(&self) -> Option<String> {
+        None
+    }
+}
+
+/// Detects the CI service that is running travis-cargo, falling back to reading the facts from
+/// the local git repository.
+pub fn detect() -> Box<dyn CiEnvironment> {
+    if var("TRAVIS").is_some() {
+        Box::new(Travis)
+    } else if var("APPVEYOR").is_some() {
+        Box::new(AppVeyor)
+    } else if var("GITHUB_ACTIONS").is_some() {
+        Box::new(GitHubActions)
+    } else if var("GITLAB_CI").is_some() {
+        Box::new(GitLabCi)
+    } else if var("TF_BUILD").is_some() {
+        Box::new(AzurePipelines)
+    } else {
+        Box::new(Local)
+    }
+}
+
+pub struct Travis;
+
+impl CiEnvironment for Travis {
+    fn name(&self) -> &'static str {
+        "travis-ci"
+    }
+
+    fn branch(&self) -> Option<String> {
+        // `TRAVIS_BRANCH` is the target branch for pull requests.
+        var("TRAVIS_PULL_REQUEST_BRANCH").or_else(|| var("TRAVIS_BRANCH"))
+    }
+
+    fn repo_slug(&self) -> Option<String> {
+        var("TRAVIS_REPO_SLUG")
+    }
+
+    fn pull_request(&self) -> Option<String> {
+        // this is either `false` or the number of the pull request.
+        var("TRAVIS_PULL_REQUEST").and_then(|pr| if pr == "false" { None } else { Some(pr) })
+    }
+
+    fn job_id(&self) -> Option<String> {
+        var("TRAVIS_JOB_ID")
+    }
+
+    fn build_number(&self) -> Option<String> {
+        var("TRAVIS_BUILD_NUMBER")
+    }
+
+    fn build_url(&self) -> Option<String> {
+        var("TRAVIS_BUILD_WEB_URL")
+    }
+
+    fn commit(&self) -> Option<String> {
+        var("TRAVIS_COMMIT")
+    }
+
+    fn tag(&self) -> Option<String> {
+        var("TRAVIS_TAG")
+    }
+
+    fn is_secure_env_available(&self) -> bool {
+        var("TRAVIS_SECURE_ENV_VARS").map(|secure| secure == "true").unwrap_or(false)
+    }
+
+    fn rust_version(&self) -> Option<String> {
+        var("TRAVIS_RUST_VERSION")
+    }
+}
+
+pub struct AppVeyor;
+
+impl CiEnvironment for AppVeyor {
+    fn name(&self) -> &'static str {
+        "appveyor"
+    }
+
+    fn branch(&self) -> Option<String> {
+        var("APPVEYOR_PULL_REQUEST_HEAD_REPO_BRANCH").or_else(|| var("APPVEYOR_REPO_BRANCH"))
+    }
+
+    fn repo_slug(&self) -> Option<String> {
+        var("APPVEYOR_REPO_NAME")
+    }
+
+    fn pull_request(&self) -> Option<String> {
+        var("APPVEYOR_PULL_REQUEST_NUMBER")
+    }
+
+    fn job_id(&self) -> Option<String> {
+        var("APPVEYOR_JOB_ID")
+    }
+
+    fn build_number(&self) -> Option<String> {
+        var("APPVEYOR_BUILD_NUMBER")
+    }
+
+    fn build_url(&self) -> Option<String> {
+        match (var("APPVEYOR_URL"),
+               var("APPVEYOR_ACCOUNT_NAME"),
+               var("APPVEYOR_PROJECT_SLUG"),
+               var("APPVEYOR_BUILD_VERSION")) {
+            (Some(url), Some(account), Some(project), Some(version)) => {
+                Some(format!("{}/project/{}/{}/build/{}", url, account, project, version))
+            }
+            _ => None,
+        }
+    }
+
+    fn commit(&self) -> Option<String> {
+        var("APPVEYOR_REPO_COMMIT")
+    }
+
+    fn tag(&self) -> Option<String> {
+        var("APPVEYOR_REPO_TAG_NAME")
+    }
+
+    fn is_secure_env_available(&self) -> bool {
+        // AppVeyor never decrypts secure variables for pull requests.
+        self.pull_request().is_none()
+    }
+}
+
+pub struct GitHubActions;
+
+impl CiEnvironment for GitHubActions {
+    fn name(&self) -> &'static str {
+        "github"
+    }
+
+    fn branch(&self) -> Option<String> {
+        var("GITHUB_HEAD_REF").or_else(|| {
+            var("GITHUB_REF").and_then(|git_ref| strip_prefix(&git_ref, "refs/heads/"))
+        })
+    }
+
+    fn repo_slug(&self) -> Option<String> {
+        var("GITHUB_REPOSITORY")
+    }
+
+    fn pull_request(&self) -> Option<String> {
+        // pull request builds check out `refs/pull/<number>/merge`.
+        var("GITHUB_REF")
+            .and_then(|git_ref| strip_prefix(&git_ref, "refs/pull/"))
+            .and_then(|git_ref| git_ref.split('/').next().map(String::from))
+    }
+
+    fn job_id(&self) -> Option<String> {
+        var("GITHUB_RUN_ID")
+    }
+
+    fn build_number(&self) -> Option<String> {
+        var("GITHUB_RUN_NUMBER")
+    }
+
+    fn build_url(&self) -> Option<String> {
+        match (var("GITHUB_REPOSITORY"), var("GITHUB_RUN_ID")) {
+            (Some(repository), Some(run_id)) => {
+                let server = var("GITHUB_SERVER_URL").unwrap_or("https://github.com".to_owned());
+                Some(format!("{}/{}/actions/runs/{}", server, repository, run_id))
+            }
+            _ => None,
+        }
+    }
+
+    fn commit(&self) -> Option<String> {
+        var("GITHUB_SHA")
+    }
+
+    fn tag(&self) -> Option<String> {
+        var("GITHUB_REF").and_then(|git_ref| strip_prefix(&git_ref, "refs/tags/"))
+    }
+
+    fn is_secure_env_available(&self) -> bool {
+        // Secrets are withheld from pull requests from forks, and there's no way to tell those
+        // apart from the environment alone.
+        var("GITHUB_EVENT_NAME").map(|event| event != "pull_request").unwrap_or(true)
+    }
+}
+
+pub struct GitLabCi;
+
+impl CiEnvironment for GitLabCi {
+    fn name(&self) -> &'static str {
+        "gitlab-ci"
+    }
+
+    fn branch(&self) -> Option<String> {
+        var("CI_MERGE_REQUEST_SOURCE_BRANCH_NAME").or_else(|| var("CI_COMMIT_BRANCH"))
+    }
+
+    fn repo_slug(&self) -> Option<String> {
+        var("CI_PROJECT_PATH")
+    }
+
+    fn pull_request(&self) -> Option<String> {
+        var("CI_MERGE_REQUEST_IID")
+    }
+
+    fn job_id(&self) -> Option<String> {
+        var("CI_JOB_ID")
+    }
+
+    fn build_number(&self) -> Option<String> {
+        var("CI_PIPELINE_IID")
+    }
+
+    fn build_url(&self) -> Option<String> {
+        var("CI_PIPELINE_URL")
+    }
+
+    fn commit(&self) -> Option<String> {
+        var("CI_COMMIT_SHA")
+    }
+
+    fn tag(&self) -> Option<String> {
+        var("CI_COMMIT_TAG")
+    }
+
+    fn is_secure_env_available(&self) -> bool {
+        // protected variables are only passed to pipelines for protected branches and tags.
+        var("CI_COMMIT_REF_PROTECTED").map(|protected| protected == "true").unwrap_or(false)
+    }
+}
+
+pub struct AzurePipelines;
+
+impl CiEnvironment for AzurePipelines {
+    fn name(&self) -> &'static str {
+        "azure-pipelines"
+    }
+
+    fn branch(&self) -> Option<String> {
+        var("SYSTEM_PULLREQUEST_SOURCEBRANCH")
+            .or_else(|| var("BUILD_SOURCEBRANCH"))
+            .map(|branch| strip_prefix(&branch, "refs/heads/").unwrap_or(branch))
+            .and_then(|branch| if branch.starts_with("refs/") { None } else { Some(branch) })
+    }
+
+    fn repo_slug(&self) -> Option<String> {
+        var("BUILD_REPOSITORY_NAME")
+    }
+
+    fn pull_request(&self) -> Option<String> {
+        var("SYSTEM_PULLREQUEST_PULLREQUESTNUMBER").or_else(|| {
+            var("SYSTEM_PULLREQUEST_PULLREQUESTID")
+        })
+    }
+
+    fn job_id(&self) -> Option<String> {
+        var("SYSTEM_JOBID")
+    }
+
+    fn build_number(&self) -> Option<String> {
+        var("BUILD_BUILDNUMBER")
+    }
+
+    fn build_url(&self) -> Option<String> {
+        match (var("SYSTEM_TEAMFOUNDATIONCOLLECTIONURI"),
+               var("SYSTEM_TEAMPROJECT"),
+               var("BUILD_BUILDID")) {
+            (Some(collection), Some(project), Some(build_id)) => {
+                Some(format!("{}{}/_build/results?buildId={}", collection, project, build_id))
+            }
+            _ => None,
+        }
+    }
+
+    fn commit(&self) -> Option<String> {
+        var("BUILD_SOURCEVERSION")
+    }
+
+    fn tag(&self) -> Option<String> {
+        var("BUILD_SOURCEBRANCH").and_then(|git_ref| strip_prefix(&git_ref, "refs/tags/"))
+    }
+
+    fn is_secure_env_available(&self) -> bool {
+        var("SYSTEM_PULLREQUEST_ISFORK").map(|fork| fork != "True").unwrap_or(true)
+    }
+}
+
+/// A build outside of any CI service, e.g. on a developer's machine. The facts are read from the
+/// git repository in the current directory instead.
+pub struct Local;
+
+impl CiEnvironment for Local {
+    fn name(&self) -> &'static str {
+        "local"
+    }
+
+    fn branch(&self) -> Option<String> {
+        // `HEAD` means that it's detached.
+        git(&["rev-parse", "--abbrev-ref", "HEAD"]).and_then(|branch| {
+            if branch == "HEAD" { None } else { Some(branch) }
+        })
+    }
+
+    fn repo_slug(&self) -> Option<String> {
+        git(&["config", "--get", "remote.origin.url"]).and_then(|url| repo_slug_from_url(&url))
+    }
+
+    fn pull_request(&self) -> Option<String> {
+        None
+    }
+
+    fn job_id(&self) -> Option<String> {
+        None
+    }
+
+    fn build_number(&self) -> Option<String> {
+        None
+    }
+
+    fn build_url(&self) -> Option<String> {
+        None
+    }
+
+    fn commit(&self) -> Option<String> {
+        git(&["rev-parse", "HEAD"])
+    }
+
+    fn tag(&self) -> Option<String> {
+        git(&["describe", "--tags", "--exact-match", "HEAD"])
+    }
+
+    fn is_secure_env_available(&self) -> bool {
+        true
+    }
+}
+
+/// Reads an environment variable, treating an empty value as unset.
+fn var(name: &str) -> Option<String> {
+    env::var(name).ok().and_then(|value| if value.is_empty() { None } else { Some(value) })
+}
+
+fn strip_prefix(s: &str, prefix: &str) -> Option<String> {
+    if s.starts_with(prefix) {
+        Some(s[prefix.len()..].to_owned())
+    } else {
+        None
+    }
+}
+
+/// Extracts `owner/name` from a remote URL such as `https://github.com/owner/name.git` or
+/// `git@github.com:owner/name.git`.
+fn repo_slug_from_url(url: &str) -> Option<String> {
+    let url = url.trim_end_matches('/');
+    let url = if url.ends_with(".git") { &url[..url.len() - 4] } else { url };
+
+    let mut parts = url.rsplit(|c| c == '/' || c == ':');
+    match (parts.next(), parts.next()) {
+        (Some(name), Some(owner)) if !name.is_empty() && !owner.is_empty() => {
+            Some(format!("{}/{}", owner, name))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::ffi::OsString;
+    use std::sync::Mutex;
+
+    /// Held while a test changes the environment, which the tests share.
+    static ENV: Mutex<()> = Mutex::new(());
+
+    /// Runs `f` with the variables set, one test at a time. An empty value unsets a variable as
+    /// far as `var` is concerned, so each test gives every variable its service reads. A later
+    /// value of a variable takes precedence.
+    fn with_env<F: FnOnce()>(vars: &[(&str, &str)], f: F) {
+        let _lock = ENV.lock().unwrap_or_else(|err| err.into_inner());
+        let saved: Vec<(&str, Option<OsString>)> =
+            vars.iter().map(|&(name, _)| (name, env::var_os(name))).collect();
+        for &(name, value) in vars {
+            env::set_var(name, value);
+        }
+        f();
+        for (name, value) in saved {
+            match value {
+                Some(value) => env::set_var(name, value),
+                None => env::remove_var(name),
+            }
+        }
+    }
+
+    fn facts(ci: &dyn CiEnvironment) -> Vec<Option<String>> {
+        vec![ci.branch(),
+             ci.repo_slug(),
+             ci.pull_request(),
+             ci.job_id(),
+             ci.build_number(),
+             ci.build_url(),
+             ci.commit(),
+             ci.tag()]
+    }
+
+    fn some(facts: &[&str]) -> Vec<Option<String>> {
+        facts.iter()
+             .map(|fact| if fact.is_empty() { None } else { Some(fact.to_string()) })
+             .collect()
+    }
+
+    #[test]
+    fn travis() {
+        let vars = [("TRAVIS_BRANCH", "master"),
+                    ("TRAVIS_PULL_REQUEST_BRANCH", ""),
+                    ("TRAVIS_REPO_SLUG", "owner/repo"),
+                    ("TRAVIS_PULL_REQUEST", "false"),
+                    ("TRAVIS_JOB_ID", "42"),
+                    ("TRAVIS_BUILD_NUMBER", "7"),
+                    ("TRAVIS_BUILD_WEB_URL", "https://travis-ci.org/owner/repo/builds/1"),
+                    ("TRAVIS_COMMIT", "abc123"),
+                    ("TRAVIS_TAG", ""),
+                    ("TRAVIS_SECURE_ENV_VARS", "true"),
+                    ("TRAVIS_RUST_VERSION", "nightly")];
+        with_env(&vars, || {
+            assert_eq!(facts(&Travis),
+                       some(&["master",
+                              "owner/repo",
+                              "",
+                              "42",
+                              "7",
+                              "https://travis-ci.org/owner/repo/builds/1",
+                              "abc123",
+                              ""]));
+            assert!(Travis.is_secure_env_available());
+            assert_eq!(Travis.rust_version(), Some("nightly".to_owned()));
+        });
+
+        let pull_request = [("TRAVIS_BRANCH", "master"),
+                            ("TRAVIS_PULL_REQUEST_BRANCH", "feature"),
+                            ("TRAVIS_PULL_REQUEST", "12"),
+                            ("TRAVIS_SECURE_ENV_VARS", "false")];
+        with_env(&[&vars[..], &pull_request[..]].concat(), || {
+            assert_eq!(Travis.branch(), Some("feature".to_owned()));
+            assert_eq!(Travis.pull_request(), Some("12".to_owned()));
+            assert!(!Travis.is_secure_env_available());
+        });
+    }
+
+    #[test]
+    fn appveyor() {
+        let vars = [("APPVEYOR_PULL_REQUEST_HEAD_REPO_BRANCH", ""),
+                    ("APPVEYOR_REPO_BRANCH", "master"),
+                    ("APPVEYOR_REPO_NAME", "owner/repo"),
+                    ("APPVEYOR_PULL_REQUEST_NUMBER", ""),
+                    ("APPVEYOR_JOB_ID", "job"),
+                    ("APPVEYOR_BUILD_NUMBER", "7"),
+                    ("APPVEYOR_URL", "https://ci.appveyor.com"),
+                    ("APPVEYOR_ACCOUNT_NAME", "owner"),
+                    ("APPVEYOR_PROJECT_SLUG", "repo"),
+                    ("APPVEYOR_BUILD_VERSION", "1.0.7"),
+                    ("APPVEYOR_REPO_COMMIT", "abc123"),
+                    ("APPVEYOR_REPO_TAG_NAME", "v1.0.0")];
+        with_env(&vars, || {
+            assert_eq!(facts(&AppVeyor),
+                       some(&["master",
+                              "owner/repo",
+                              "",
+                              "job",
+                              "7",
+                              "https://ci.appveyor.com/project/owner/repo/build/1.0.7",
+                              "abc123",
+                              "v1.0.0"]));
+            assert!(AppVeyor.is_secure_env_available());
+        });
+
+        let pull_request = [("APPVEYOR_PULL_REQUEST_HEAD_REPO_BRANCH", "feature"),
+                            ("APPVEYOR_PULL_REQUEST_NUMBER", "12"),
+                            ("APPVEYOR_ACCOUNT_NAME", "")];
+        with_env(&[&vars[..], &pull_request[..]].concat(), || {
+            assert_eq!(AppVeyor.branch(), Some("feature".to_owned()));
+            assert_eq!(AppVeyor.pull_request(), Some("12".to_owned()));
+            assert_eq!(AppVeyor.build_url(), None);
+            assert!(!AppVeyor.is_secure_env_available());
+        });
+    }
+
+    #[test]
+    fn github_actions() {
+        let vars = [("GITHUB_HEAD_REF", ""),
+                    ("GITHUB_REF", "refs/heads/master"),
+                    ("GITHUB_REPOSITORY", "owner/repo"),
+                    ("GITHUB_RUN_ID", "1234"),
+                    ("GITHUB_RUN_NUMBER", "7"),
+                    ("GITHUB_SERVER_URL", ""),
+                    ("GITHUB_SHA", "abc123"),
+                    ("GITHUB_EVENT_NAME", "push")];
+        with_env(&vars, || {
+            assert_eq!(facts(&GitHubActions),
+                       some(&["master",
+                              "owner/repo",
+                              "",
+                              "1234",
+                              "7",
+                              "https://github.com/owner/repo/actions/runs/1234",
+                              "abc123",
+                              ""]));
+            assert!(GitHubActions.is_secure_env_available());
+        });
+
+        let tag = [("GITHUB_REF", "refs/tags/v1.0.0"),
+                   ("GITHUB_SERVER_URL", "https://github.example.com")];
+        with_env(&[&vars[..], &tag[..]].concat(), || {
+            assert_eq!(GitHubActions.branch(), None);
+            assert_eq!(GitHubActions.tag(), Some("v1.0.0".to_owned()));
+            assert_eq!(GitHubActions.build_url(),
+                       Some("https://github.example.com/owner/repo/actions/runs/1234".to_owned()));
+        });
+
+        let pull_request = [("GITHUB_HEAD_REF", "feature"),
+                            ("GITHUB_REF", "refs/pull/12/merge"),
+                            ("GITHUB_EVENT_NAME", "pull_request")];
+        with_env(&[&vars[..], &pull_request[..]].concat(), || {
+            assert_eq!(GitHubActions.branch(), Some("feature".to_owned()));
+            assert_eq!(GitHubActions.pull_request(), Some("12".to_owned()));
+            assert_eq!(GitHubActions.tag(), None);
+            assert!(!GitHubActions.is_secure_env_available());
+        });
+    }
+
+    #[test]
+    fn gitlab_ci() {
+        let vars = [("CI_MERGE_REQUEST_SOURCE_BRANCH_NAME", ""),
+                    ("CI_COMMIT_BRANCH", "master"),
+                    ("CI_PROJECT_PATH", "group/project"),
+                    ("CI_MERGE_REQUEST_IID", ""),
+                    ("CI_JOB_ID", "42"),
+                    ("CI_PIPELINE_IID", "7"),
+                    ("CI_PIPELINE_URL", "https://gitlab.com/group/project/-/pipelines/1"),
+                    ("CI_COMMIT_SHA", "abc123"),
+                    ("CI_COMMIT_TAG", ""),
+                    ("CI_COMMIT_REF_PROTECTED", "true")];
+        with_env(&vars, || {
+            assert_eq!(facts(&GitLabCi),
+                       some(&["master",
+                              "group/project",
+                              "",
+                              "42",
+                              "7",
+                              "https://gitlab.com/group/project/-/pipelines/1",
+                              "abc123",
+                              ""]));
+            assert!(GitLabCi.is_secure_env_available());
+        });
+
+        let merge_request = [("CI_MERGE_REQUEST_SOURCE_BRANCH_NAME", "feature"),
+                             ("CI_COMMIT_BRANCH", ""),
+                             ("CI_MERGE_REQUEST_IID", "12"),
+                             ("CI_COMMIT_REF_PROTECTED", "false")];
+        with_env(&[&vars[..], &merge_request[..]].concat(), || {
+            assert_eq!(GitLabCi.branch(), Some("feature".to_owned()));
+            assert_eq!(GitLabCi.pull_request(), Some("12".to_owned()));
+            assert!(!GitLabCi.is_secure_env_available());
+        });
+    }
+
+    #[test]
+    fn azure_pipelines() {
+        let vars = [("SYSTEM_PULLREQUEST_SOURCEBRANCH", ""),
+                    ("BUILD_SOURCEBRANCH", "refs/heads/master"),
+                    ("BUILD_REPOSITORY_NAME", "owner/repo"),
+                    ("SYSTEM_PULLREQUEST_PULLREQUESTNUMBER", ""),
+                    ("SYSTEM_PULLREQUEST_PULLREQUESTID", ""),
+                    ("SYSTEM_JOBID", "job"),
+                    ("BUILD_BUILDNUMBER", "20200101.1"),
+                    ("SYSTEM_TEAMFOUNDATIONCOLLECTIONURI", "https://dev.azure.com/owner/"),
+                    ("SYSTEM_TEAMPROJECT", "repo"),
+                    ("BUILD_BUILDID", "7"),
+                    ("BUILD_SOURCEVERSION", "abc123"),
+                    ("SYSTEM_PULLREQUEST_ISFORK", "")];
+        with_env(&vars, || {
+            assert_eq!(facts(&AzurePipelines),
+                       some(&["master",
+                              "owner/repo",
+                              "",
+                              "job",
+                              "20200101.1",
+                              "https://dev.azure.com/owner/repo/_build/results?buildId=7",
+                              "abc123",
+                              ""]));
+            assert!(AzurePipelines.is_secure_env_available());
+        });
+
+        let tag = [("BUILD_SOURCEBRANCH", "refs/tags/v1.0.0")];
+        with_env(&[&vars[..], &tag[..]].concat(), || {
+            assert_eq!(AzurePipelines.branch(), None);
+            assert_eq!(AzurePipelines.tag(), Some("v1.0.0".to_owned()));
+        });
+
+        let pull_request = [("SYSTEM_PULLREQUEST_SOURCEBRANCH", "refs/heads/feature"),
+                            ("BUILD_SOURCEBRANCH", "refs/pull/12/merge"),
+                            ("SYSTEM_PULLREQUEST_PULLREQUESTID", "345"),
+                            ("SYSTEM_PULLREQUEST_ISFORK", "True")];
+        with_env(&[&vars[..], &pull_request[..]].concat(), || {
+            assert_eq!(AzurePipelines.branch(), Some("feature".to_owned()));
+            assert_eq!(AzurePipelines.pull_request(), Some("345".to_owned()));
+            assert_eq!(AzurePipelines.tag(), None);
+            assert!(!AzurePipelines.is_secure_env_available());
+        });
+    }
+
+    #[test]
+    fn repo_slugs() {
+        for url in &["https://github.com/owner/repo",
+                     "https://github.com/owner/repo.git",
+                     "https://github.com/owner/repo/",
+                     "git@github.com:owner/repo.git",
+                     "ssh://git@github.com/owner/repo.git"] {
+            assert_eq!(repo_slug_from_url(url), Some("owner/repo".to_owned()), "{}", url);
+        }
+        assert_eq!(repo_slug_from_url("repo"), None);
+        assert_eq!(repo_slug_from_url(".git"), None);
+    }
+}
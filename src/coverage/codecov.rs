@@ -0,0 +1,131 @@
+use std::io::prelude::*;
+use std::path::Path;
+use std::process::Command;
+
+use CiEnvironment;
+use error::{Error, Result};
+use utils;
+
+use super::report::Report;
+
+/// What is uploaded along with the report, see <https://docs.codecov.io/reference#upload>.
+#[derive(Debug, Clone, Default)]
+pub struct Upload {
+    pub token: Option<String>,
+    /// Flags to group the report under, such as `unittests`.
+    pub flags: Vec<String>,
+    /// A name for the upload, shown in Codecov's UI.
+    pub name: Option<String>,
+}
+
+/// The query string of the upload request.
+pub fn query(ci: &dyn CiEnvironment, upload: &Upload) -> Result<Vec<(&'static str, String)>> {
+    let commit = try!(ci.commit()
+                        .or_else(|| utils::git(&["rev-parse", "HEAD"]))
+                        .ok_or(Error::MissingCiInfo("commit")));
+
+    let mut query = vec![("package", format!("travis-cargo-{}", env!("CARGO_PKG_VERSION"))),
+                         ("commit", commit)];
+    push(&mut query, "token", upload.token.clone());
+    push(&mut query, "service", service(ci).map(String::from));
+    push(&mut query, "branch", ci.branch());
+    push(&mut query, "pr", ci.pull_request());
+    push(&mut query, "tag", ci.tag());
+    push(&mut query, "slug", ci.repo_slug());
+    push(&mut query, "build", ci.build_number());
+    push(&mut query, "job", ci.job_id());
+    push(&mut query, "build_url", ci.build_url());
+    push(&mut query, "name", upload.name.clone());
+    if !upload.flags.is_empty() {
+        push(&mut query, "flags", Some(upload.flags.join(",")));
+    }
+    Ok(query)
+}
+
+fn push(query: &mut Vec<(&'static str, String)>, key: &'static str, value: Option<String>) {
+    if let Some(value) = value {
+        query.push((key, value));
+    }
+}
+
+/// Writes the report in the format of Codecov's uploaders: the files of the repository, followed
+/// by the coverage files.
+pub fn write_payload<W: Write>(report: &Report, out: &mut W, source_root: &Path) -> Result<()> {
+    for path in report.files.keys() {
+        let name = path.strip_prefix(source_root).unwrap_or(path);
+        try!(writeln!(out, "{}", name.display()));
+    }
+    try!(writeln!(out, "<<<<<< network"));
+    try!(writeln!(out, "# path=lcov.info"));
+    try!(report.write_lcov(out));
+    try!(writeln!(out, "<<<<<< EOF"));
+    Ok(())
+}
+
+/// Uploads the payload in two steps: Codecov answers the first request with the URL of the
+/// report, and where to put the payload.
+pub fn upload(url: &str, query: &[(&'static str, String)], payload: &Path) -> Result<()> {
+    let query = query.iter()
+                     .map(|&(key, ref value)| format!("{}={}", key, percent_encode(value)))
+                     .collect::<Vec<_>>()
+                     .join("&");
+    let url = format!("{}/upload/v4?{}", url.trim_end_matches('/'), query);
+
+    let response = try!(curl(&[("url", &url), ("header", "Accept: text/plain"), ("data", "")]));
+    let mut lines = response.lines().map(str::trim);
+    let (report_url, upload_url) = match (lines.next(), lines.next()) {
+        (Some(report_url), Some(upload_url)) if !upload_url.is_empty() => (report_url, upload_url),
+        _ => return Err(Error::UploadFailed(response.trim().to_owned())),
+    };
+
+    try!(curl(&[("url", upload_url),
+                ("request", "PUT"),
+                ("header", "Content-Type: text/plain"),
+                ("data-binary", &format!("@{}", payload.display()))]));
+
+    println!("Coverage is at {}", report_url);
+    Ok(())
+}
+
+/// Runs curl with the options given through its stdin, which keeps the token that is part of the
+/// URL out of the command line.
+fn curl(options: &[(&str, &str)]) -> Result<String> {
+    let mut config = String::new();
+    for &(option, value) in options {
+        config.push_str(&format!("{} = \"{}\"\n",
+                                 option,
+                                 value.replace('\\', "\\\\").replace('"', "\\\"")));
+    }
+
+    utils::run_input(Command::new("curl").args(&["--silent",
+                                                 "--show-error",
+                                                 "--fail",
+                                                 "--config",
+                                                 "-"]),
+                     config.as_bytes())
+}
+
+/// Codecov's names for the CI services.
+fn service(ci: &dyn CiEnvironment) -> Option<&'static str> {
+    match ci.name() {
+        "travis-ci" => Some("travis"),
+        "appveyor" => Some("appveyor"),
+        "github" => Some("github-actions"),
+        "gitlab-ci" => Some("gitlab"),
+        "azure-pipelines" => Some("azure_pipelines"),
+        _ => None,
+    }
+}
+
+fn percent_encode(s: &str) -> String {
+    let mut encoded = String::new();
+    for &byte in s.as_bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
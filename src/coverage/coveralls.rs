@@ -0,0 +1,150 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::Path;
+use std::process::Command;
+
+use rustc_serialize::json::Json;
+
+use CiEnvironment;
+use error::{Error, Result};
+use utils;
+
+use super::md5;
+use super::report::Report;
+
+/// Builds the job that is uploaded to `/api/v1/jobs`, see
+/// <https://docs.coveralls.io/api-reference>.
+///
+/// Coveralls identifies the repository by the `repo_token`, or for public repositories on
+/// Travis, by the `service_job_id`.
+pub fn job(report: &Report,
+           ci: &dyn CiEnvironment,
+           source_root: &Path,
+           repo_token: Option<&str>,
+           parallel: bool)
+           -> Result<Json> {
+    let mut source_files = vec![];
+    for (path, coverage) in &report.files {
+        let mut source = vec![];
+        try!(File::open(path).and_then(|mut file| file.read_to_end(&mut source)));
+
+        // One entry per line of the source, `null` for the lines without any code.
+        let line_count = String::from_utf8_lossy(&source).lines().count();
+        let last_line = coverage.lines.keys().next_back().map_or(0, |&line| line as usize);
+        let mut hits = vec![Json::Null; line_count.max(last_line)];
+        for (&line, &count) in &coverage.lines {
+            if line > 0 {
+                hits[line as usize - 1] = Json::U64(count);
+            }
+        }
+
+        let name = path.strip_prefix(source_root).unwrap_or(path);
+        let mut file = BTreeMap::new();
+        file.insert("name".to_owned(), Json::String(name.display().to_string()));
+        file.insert("source_digest".to_owned(), Json::String(md5::hex_digest(&source)));
+        file.insert("coverage".to_owned(), Json::Array(hits));
+        source_files.push(Json::Object(file));
+    }
+
+    let mut job = BTreeMap::new();
+    job.insert("service_name".to_owned(), Json::String(ci.name().to_owned()));
+    insert(&mut job, "service_job_id", ci.job_id());
+    insert(&mut job, "service_number", ci.build_number());
+    insert(&mut job, "service_pull_request", ci.pull_request());
+    insert(&mut job, "repo_token", repo_token.map(String::from));
+    if parallel {
+        job.insert("parallel".to_owned(), Json::Boolean(true));
+    }
+    if let Some(git) = git_info(ci) {
+        job.insert("git".to_owned(), git);
+    }
+    job.insert("source_files".to_owned(), Json::Array(source_files));
+
+    Ok(Json::Object(job))
+}
+
+pub fn upload(endpoint: &str, job: &Json) -> Result<()> {
+    let url = format!("{}/api/v1/jobs", endpoint.trim_end_matches('/'));
+
+    // The job is piped in, since it may contain the repo token.
+    let response = try!(utils::run_input(Command::new("curl")
+                                             .args(&["--silent", "--show-error", "--fail"])
+                                             .arg("--form")
+                                             .arg("json_file=@-;filename=coveralls.json")
+                                             .arg(&url),
+                                         job.to_string().as_bytes()));
+    print_response(&response);
+    Ok(())
+}
+
+/// Tells coveralls that all of the parallel jobs of the build have been uploaded, so that it can
+/// combine them.
+pub fn finish(endpoint: &str, ci: &dyn CiEnvironment, repo_token: Option<&str>) -> Result<()> {
+    let build_number = try!(ci.build_number().ok_or(Error::MissingCiInfo("build number")));
+    let url = format!("{}/webhook", endpoint.trim_end_matches('/'));
+
+    let mut payload = BTreeMap::new();
+    payload.insert("build_num".to_owned(), Json::String(build_number));
+    payload.insert("status".to_owned(), Json::String("done".to_owned()));
+
+    let mut body = BTreeMap::new();
+    body.insert("service_name".to_owned(), Json::String(ci.name().to_owned()));
+    insert(&mut body, "repo_name", ci.repo_slug());
+    insert(&mut body, "repo_token", repo_token.map(String::from));
+    body.insert("payload".to_owned(), Json::Object(payload));
+
+    let response = try!(utils::run_input(Command::new("curl")
+                                             .args(&["--silent", "--show-error", "--fail"])
+                                             .args(&["--header", "Content-Type: application/json"])
+                                             .args(&["--data-binary", "@-"])
+                                             .arg(&url),
+                                         Json::Object(body).to_string().as_bytes()));
+    print_response(&response);
+    Ok(())
+}
+
+fn insert(object: &mut BTreeMap<String, Json>, key: &str, value: Option<String>) {
+    if let Some(value) = value {
+        object.insert(key.to_owned(), Json::String(value));
+    }
+}
+
+fn git_info(ci: &dyn CiEnvironment) -> Option<Json> {
+    let commit = ci.commit().unwrap_or("HEAD".to_owned());
+    let log = match utils::git(&["log", "-1", "--format=%H%n%an%n%ae%n%cn%n%ce%n%s", &commit]) {
+        Some(log) => log,
+        None => return None,
+    };
+
+    let keys = ["id", "author_name", "author_email", "committer_name", "committer_email",
+                "message"];
+    let mut head = BTreeMap::new();
+    for (key, value) in keys.iter().zip(log.lines()) {
+        head.insert(key.to_string(), Json::String(value.to_owned()));
+    }
+
+    let mut remotes = vec![];
+    for line in utils::git(&["remote", "-v"]).unwrap_or_default().lines() {
+        let fields: Vec<_> = line.split_whitespace().collect();
+        if fields.len() == 3 && fields[2] == "(fetch)" {
+            let mut remote = BTreeMap::new();
+            remote.insert("name".to_owned(), Json::String(fields[0].to_owned()));
+            remote.insert("url".to_owned(), Json::String(fields[1].to_owned()));
+            remotes.push(Json::Object(remote));
+        }
+    }
+
+    let mut git = BTreeMap::new();
+    git.insert("head".to_owned(), Json::Object(head));
+    insert(&mut git, "branch", ci.branch());
+    git.insert("remotes".to_owned(), Json::Array(remotes));
+    Some(Json::Object(git))
+}
+
+fn print_response(response: &str) {
+    match Json::from_str(response).ok().as_ref().and_then(|json| json.find("url")) {
+        Some(&Json::String(ref url)) => println!("Coverage is at {}", url),
+        _ => println!("{}", response.trim()),
+    }
+}
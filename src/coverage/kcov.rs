@@ -0,0 +1,319 @@
+use std::cmp;
+use std::env;
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::vec;
+
+use Manifest;
+use error::{Error, Result};
+use utils;
+
+use super::{Recording, TestBinary};
+use super::sha256;
+
+/// The release of kcov that is built when it isn't installed, and the SHA-256 checksum of its
+/// source archive, <https://github.com/SimonKagstrom/kcov/archive/v42.tar.gz>. The checksum is the
+/// one that Homebrew's kcov formula pinned for this release.
+const KCOV_VERSION: &'static str = "42";
+const KCOV_SHA256: &'static str =
+    "2c47d75397af248bc387f60cdd79180763e1f88f3dd71c94bb52478f8e74a1f8";
+
+pub struct Options {
+    /// The kcov binary to use, from `--kcov`.
+    pub kcov: Option<PathBuf>,
+    /// A downloaded source archive of `KCOV_VERSION`, to build kcov without network access.
+    pub kcov_source: Option<PathBuf>,
+    pub use_sudo: bool,
+    pub verify: bool,
+    /// How many binaries to record at once.
+    pub jobs: usize,
+    /// Arguments for recording each binary.
+    pub extra_args: Vec<String>,
+}
+
+/// Records the coverage of each test binary and merges it. With `--no-fail-fast`, the coverage
+/// is merged even if some binaries fail, and the first failure is returned inside, after the
+/// merge; otherwise it stops recording and is returned right away.
+pub fn record(manifest: &Manifest,
+              options: &Options,
+              test_binaries: &[TestBinary],
+              recording: &Recording)
+              -> Result<Result<()>> {
+    if test_binaries.is_empty() {
+        return Err(Error::NoTestBinaries);
+    }
+    let kcov = try!(find_kcov(options));
+
+    let target_dir = manifest.target_directory();
+
+    let exclude_pattern_arg: String = {
+        let exclude_pattern_arg = "--exclude-pattern=/.cargo";
+        if let Some(ref additional_exclude) = recording.exclude_pattern {
+            format!("{},{}", exclude_pattern_arg, additional_exclude)
+        } else {
+            exclude_pattern_arg.to_owned()
+        }
+    };
+
+    let target_libdir = try!(utils::run_output(Command::new("rustc")
+                                                   .args(&["--print", "target-libdir"])));
+    let cargo = env::var_os("CARGO")
+                    .map(PathBuf::from)
+                    .or_else(|| utils::find_in_path("cargo"));
+
+    let mut invocations = vec![];
+    for test_binary in test_binaries.iter() {
+        let binary = &test_binary.executable;
+        let mut kcov_args: Vec<String> = options.extra_args.clone();
+
+        if options.verify {
+            kcov_args.push("--verify".to_owned());
+        }
+
+        kcov_args.push(exclude_pattern_arg.clone());
+        kcov_args.push(kcov_dir(&target_dir, binary));
+        kcov_args.push(binary.to_str().unwrap().to_owned());
+        kcov_args.extend(recording.harness_args.iter().cloned());
+
+        // Run the binary like `cargo test` would: from its package's directory, with the
+        // package's variables and the libraries it was built against.
+        let mut command = Command::new(&kcov);
+        command.args(&kcov_args);
+        let package = manifest.workspace_members()
+                              .iter()
+                              .find(|package| package.id == test_binary.package_id);
+        if let Some(package) = package {
+            command.current_dir(package.manifest_dir()).envs(package.test_env());
+        }
+        if let Some(ref cargo) = cargo {
+            command.env("CARGO", cargo);
+        }
+        let deps_dir = binary.parent().unwrap();
+        let library_path = [deps_dir, deps_dir.parent().unwrap(), Path::new(target_libdir.trim())];
+        command.env(LIBRARY_PATH, try!(prepend_paths(LIBRARY_PATH, &library_path)));
+
+        invocations.push(Invocation {
+            binary: binary.clone(),
+            kcov_args: kcov_args,
+            command: command,
+        });
+    }
+
+    // Record coverage for each binary, `jobs` at a time
+    let jobs = cmp::min(cmp::max(options.jobs, 1), invocations.len());
+    let queue = Queue {
+        invocations: Mutex::new(invocations.into_iter()),
+        failed: AtomicBool::new(false),
+        fail_fast: !recording.no_fail_fast,
+        buffered: jobs > 1,
+    };
+    let queue = Arc::new(queue);
+    let workers: Vec<_> = (0..jobs)
+                              .map(|_| {
+                                  let queue = queue.clone();
+                                  thread::spawn(move || record_queued(&queue))
+                              })
+                              .collect();
+
+    let mut failures = vec![];
+    for worker in workers {
+        failures.extend(worker.join().unwrap());
+    }
+    failures.sort_by(|a, b| a.0.cmp(&b.0));
+
+    if failures.is_empty() || recording.no_fail_fast {
+        // Merge all the coverages
+        println!("{}", recording.merge_message);
+        let mut kcov_args = vec!["--merge".to_owned(),
+                                 recording.merge_dir.to_str().unwrap().to_owned()];
+        for binary in test_binaries.iter() {
+            kcov_args.push(kcov_dir(&target_dir, &binary.executable));
+        }
+
+        try!(utils::run(Command::new(kcov).args(&kcov_args)));
+    }
+
+    if failures.is_empty() {
+        return Ok(Ok(()));
+    }
+    println!("Recording failed for {} of {} test binaries:",
+             failures.len(),
+             test_binaries.len());
+    for &(ref binary, ref err) in &failures {
+        println!("    {}: {}", binary.display(), err);
+    }
+    let failure = Err(failures.into_iter().next().unwrap().1);
+    if recording.no_fail_fast { Ok(failure) } else { failure.map(Ok) }
+}
+
+/// The default for `--jobs`: the number of CPUs.
+pub fn default_jobs() -> usize {
+    thread::available_parallelism().map(|jobs| jobs.get()).unwrap_or(1)
+}
+
+struct Invocation {
+    binary: PathBuf,
+    kcov_args: Vec<String>,
+    command: Command,
+}
+
+/// The kcov invocations that are left, shared by the threads recording them.
+struct Queue {
+    invocations: Mutex<vec::IntoIter<Invocation>>,
+    /// Whether any binary has failed, which stops the others from starting unless `fail_fast` is
+    /// off.
+    failed: AtomicBool,
+    fail_fast: bool,
+    buffered: bool,
+}
+
+/// Records binaries from the queue until it is empty, returning those that failed.
+fn record_queued(queue: &Queue) -> Vec<(PathBuf, Error)> {
+    let mut failures = vec![];
+    loop {
+        if queue.fail_fast && queue.failed.load(Ordering::SeqCst) {
+            return failures;
+        }
+        let next = queue.invocations.lock().unwrap().next();
+        let Invocation { binary, kcov_args, mut command } = match next {
+            Some(invocation) => invocation,
+            None => return failures,
+        };
+
+        let result = if queue.buffered {
+            // Print everything at once when it's done, so that concurrent binaries don't mix.
+            let mut output = vec![];
+            let result = utils::run_buffered(&mut command, &mut output);
+            let stdout = io::stdout();
+            let mut stdout = stdout.lock();
+            let _ = write!(stdout,
+                           "Recording {}\nRunning: kcov {}\n",
+                           binary.display(),
+                           kcov_args.join(" "));
+            let _ = stdout.write_all(&output);
+            result
+        } else {
+            println!("Recording {}", binary.display());
+            println!("Running: kcov {}", kcov_args.join(" "));
+            utils::run(&mut command)
+        };
+
+        if let Err(err) = result {
+            queue.failed.store(true, Ordering::SeqCst);
+            failures.push((binary, err));
+        }
+    }
+}
+
+/// Finds kcov: `--kcov`, then `$KCOV`, then the `PATH`, and finally a build of the pinned version
+/// in `~/.cache/travis-cargo`, which is made if it isn't there yet.
+fn find_kcov(options: &Options) -> Result<PathBuf> {
+    let explicit = options.kcov.clone().or_else(|| env::var_os("KCOV").map(PathBuf::from));
+    if let Some(kcov) = explicit {
+        return if kcov.is_file() { Ok(kcov) } else { Err(Error::KcovNotFound(kcov)) };
+    }
+
+    if let Some(kcov) = utils::find_in_path("kcov") {
+        return Ok(kcov);
+    }
+
+    let cache = Path::new(&try!(utils::env_var("HOME"))).join(".cache/travis-cargo");
+    let prefix = cache.join(format!("kcov-{}", KCOV_VERSION));
+    let kcov = prefix.join("bin/kcov");
+    if !kcov.is_file() {
+        try!(build_kcov(options, &cache, &prefix));
+    }
+
+    if !kcov.is_file() {
+        return Err(Error::KcovNotFound(kcov));
+    }
+    Ok(kcov)
+}
+
+fn build_kcov(options: &Options, cache: &Path, prefix: &Path) -> Result<()> {
+    if options.use_sudo {
+        let mut packages = vec!["libcurl4-openssl-dev", "libelf-dev", "libdw-dev", "cmake"];
+        if options.verify {
+            packages.push("binutils-dev");
+        }
+        println!("Running: sudo apt-get install {}", packages.join(" "));
+        try!(utils::run(Command::new("sudo").args(&["apt-get", "install"]).args(&packages)));
+    }
+
+    try!(fs::create_dir_all(cache));
+    let archive = match options.kcov_source {
+        Some(ref archive) => archive.clone(),
+        None => {
+            let archive = cache.join(format!("kcov-{}.tar.gz", KCOV_VERSION));
+            let url = format!("https://github.com/SimonKagstrom/kcov/archive/v{}.tar.gz",
+                              KCOV_VERSION);
+            println!("Downloading {}", url);
+            try!(utils::run(Command::new("curl")
+                                .args(&["--silent", "--show-error", "--fail", "--location"])
+                                .arg("--output")
+                                .arg(&archive)
+                                .arg(&url)));
+            archive
+        }
+    };
+
+    let mut contents = vec![];
+    try!(try!(File::open(&archive)).read_to_end(&mut contents));
+    if sha256::hex_digest(&contents) != KCOV_SHA256 {
+        return Err(Error::ChecksumMismatch(archive));
+    }
+
+    // Build outside of the project, so that it doesn't get in the way of the next run.
+    let build_dir = cache.join(format!("kcov-{}-build", KCOV_VERSION));
+    if build_dir.exists() {
+        try!(fs::remove_dir_all(&build_dir));
+    }
+    try!(fs::create_dir_all(&build_dir));
+    try!(utils::run(Command::new("tar")
+                        .args(&["--extract", "--gzip", "--strip-components=1", "--file"])
+                        .arg(&archive)
+                        .arg("--directory")
+                        .arg(&build_dir)));
+
+    let cmake_dir = build_dir.join("build");
+    try!(fs::create_dir_all(&cmake_dir));
+    println!("Building kcov {} into {}", KCOV_VERSION, prefix.display());
+    try!(utils::run(Command::new("cmake")
+                        .arg(format!("-DCMAKE_INSTALL_PREFIX={}", prefix.display()))
+                        .arg("..")
+                        .current_dir(&cmake_dir)));
+    try!(utils::run(Command::new("make").current_dir(&cmake_dir)));
+    try!(utils::run(Command::new("make").arg("install").current_dir(&cmake_dir)));
+
+    try!(fs::remove_dir_all(&build_dir));
+    Ok(())
+}
+
+/// The variable that the dynamic linker searches for libraries, like the dylibs of the standard
+/// library and of proc-macro dependencies.
+#[cfg(target_os = "macos")]
+const LIBRARY_PATH: &'static str = "DYLD_FALLBACK_LIBRARY_PATH";
+#[cfg(windows)]
+const LIBRARY_PATH: &'static str = "PATH";
+#[cfg(not(any(target_os = "macos", windows)))]
+const LIBRARY_PATH: &'static str = "LD_LIBRARY_PATH";
+
+/// The value of the path variable `var` with `paths` in front.
+fn prepend_paths(var: &str, paths: &[&Path]) -> Result<OsString> {
+    let mut joined: Vec<PathBuf> = paths.iter().map(|path| path.to_path_buf()).collect();
+    if let Some(existing) = env::var_os(var) {
+        joined.extend(env::split_paths(&existing));
+    }
+    env::join_paths(joined).map_err(|err| Error::InvalidArgument(format!("`{}`: {}", var, err)))
+}
+
+fn kcov_dir(target_dir: &Path, binary: &Path) -> String {
+    let binary_name = binary.file_stem().unwrap().to_string_lossy();
+    target_dir.join(format!("kcov-{}", binary_name)).to_str().unwrap().to_owned()
+}
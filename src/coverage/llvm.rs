@@ -0,0 +1,156 @@
+use std::env;
+use std::fs::{self, File};
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use {Manifest, Toolchain};
+use error::{Error, Result};
+use utils;
+
+use super::{Recording, test_binaries};
+
+/// Records the coverage of `cargo test` and merges it. Like `kcov::record`, a failure of the tests
+/// is returned inside with `--no-fail-fast`, once the coverage is merged.
+pub fn record(manifest: &Manifest,
+              toolchain: &Toolchain,
+              recording: &Recording)
+              -> Result<Result<()>> {
+    let llvm_profdata = try!(llvm_tool(toolchain, "llvm-profdata"));
+    let llvm_cov = try!(llvm_tool(toolchain, "llvm-cov"));
+
+    // The instrumented build gets its own target directory, so that it doesn't invalidate the
+    // regular one (and vice versa).
+    let work_dir = manifest.target_directory().join("llvm-cov-target");
+    let profraw_dir = work_dir.join("profraw");
+    let doctest_dir = work_dir.join("doctests");
+    for dir in &[&profraw_dir, &doctest_dir] {
+        if dir.exists() {
+            try!(fs::remove_dir_all(dir));
+        }
+        try!(fs::create_dir_all(dir));
+    }
+
+    // Persisting the doc tests, so that llvm-cov can find their coverage mapping, is unstable.
+    let doctests = toolchain.channel.is_nightly();
+
+    let cargo = || {
+        let mut cargo = Command::new("cargo");
+        cargo.env("CARGO_TARGET_DIR", &work_dir)
+             .env("RUSTFLAGS", append_flags("RUSTFLAGS", "-C instrument-coverage"))
+             .env("LLVM_PROFILE_FILE", profraw_dir.join("%p-%m.profraw"));
+        if doctests {
+            let persist = format!("-C instrument-coverage -Z unstable-options --persist-doctests {}",
+                                  doctest_dir.display());
+            cargo.env("RUSTDOCFLAGS", append_flags("RUSTDOCFLAGS", &persist));
+        }
+        cargo
+    };
+
+    let mut objects: Vec<_> = try!(test_binaries(cargo(), &recording.test_args))
+                                  .into_iter()
+                                  .map(|binary| binary.executable)
+                                  .collect();
+    // With `--no-fail-fast`, the failure is reported once the coverage of every test is merged.
+    let mut test = cargo();
+    test.arg("test");
+    if recording.no_fail_fast {
+        test.arg("--no-fail-fast");
+    }
+    let tested = utils::run(test.args(&recording.test_args)
+                                .arg("--")
+                                .args(&recording.harness_args));
+    if !recording.no_fail_fast && tested.is_err() {
+        return tested.map(Ok);
+    }
+    if doctests {
+        try!(find_executables(&doctest_dir, &mut objects));
+    }
+    if objects.is_empty() {
+        return Err(Error::NoTestBinaries);
+    }
+
+    let merge_dir = &recording.merge_dir;
+    println!("{}", recording.merge_message);
+    try!(fs::create_dir_all(merge_dir));
+
+    let profdata = merge_dir.join("coverage.profdata");
+    let mut profraws = vec![];
+    for entry in try!(fs::read_dir(&profraw_dir)) {
+        profraws.push(try!(entry).path());
+    }
+    try!(utils::run(Command::new(&llvm_profdata)
+                        .args(&["merge", "-sparse"])
+                        .args(&profraws)
+                        .arg("-o")
+                        .arg(&profdata)));
+
+    // Ignore the sources of dependencies and of the standard library, like kcov's default
+    // `/.cargo` pattern does.
+    let mut ignore = vec![r"/\.cargo/".to_owned(), r"^/rustc/".to_owned()];
+    if let Some(ref exclude_pattern) = recording.exclude_pattern {
+        ignore.extend(exclude_pattern.split(',').filter(|p| !p.is_empty()).map(escape_regex));
+    }
+
+    let mut llvm_cov = Command::new(&llvm_cov);
+    llvm_cov.args(&["export", "-format=lcov"])
+            .arg(format!("-instr-profile={}", profdata.display()))
+            .arg(format!("-ignore-filename-regex={}", ignore.join("|")));
+    for (i, object) in objects.iter().enumerate() {
+        if i > 0 {
+            llvm_cov.arg("-object");
+        }
+        llvm_cov.arg(object);
+    }
+    let lcov = try!(utils::run_output(&mut llvm_cov));
+
+    let mut file = try!(File::create(merge_dir.join("lcov.info")));
+    try!(file.write_all(lcov.as_bytes()));
+    Ok(tested)
+}
+
+/// Finds an LLVM tool matching the toolchain's LLVM, preferring the one installed by the
+/// `llvm-tools-preview` rustup component.
+fn llvm_tool(toolchain: &Toolchain, name: &'static str) -> Result<PathBuf> {
+    let sysroot = try!(utils::run_output(Command::new("rustc").args(&["--print", "sysroot"])));
+    let tool = Path::new(sysroot.trim())
+                   .join("lib/rustlib")
+                   .join(&toolchain.host)
+                   .join("bin")
+                   .join(name);
+    if tool.is_file() {
+        return Ok(tool);
+    }
+
+    utils::find_in_path(name).ok_or(Error::LlvmToolNotFound(name))
+}
+
+fn append_flags(var: &str, flags: &str) -> String {
+    match env::var(var) {
+        Ok(ref existing) if !existing.trim().is_empty() => format!("{} {}", existing, flags),
+        _ => flags.to_owned(),
+    }
+}
+
+fn find_executables(dir: &Path, executables: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in try!(fs::read_dir(dir)) {
+        let path = try!(entry).path();
+        if path.is_dir() {
+            try!(find_executables(&path, executables));
+        } else {
+            executables.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn escape_regex(pattern: &str) -> String {
+    let mut escaped = String::new();
+    for c in pattern.chars() {
+        if "\\.+*?()|[]{}^$".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
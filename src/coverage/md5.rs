@@ -0,0 +1,88 @@
+//! MD5, which coveralls.io uses to tell whether a source file changed between builds.
+
+const SHIFTS: [u32; 64] = [7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 7, 12, 17, 22, 5, 9, 14,
+                           20, 5, 9, 14, 20, 5, 9, 14, 20, 5, 9, 14, 20, 4, 11, 16, 23, 4, 11, 16,
+                           23, 4, 11, 16, 23, 4, 11, 16, 23, 6, 10, 15, 21, 6, 10, 15, 21, 6, 10,
+                           15, 21, 6, 10, 15, 21];
+
+/// The lowercase hex digest of `data`.
+pub fn hex_digest(data: &[u8]) -> String {
+    // the integer part of abs(sin(i + 1)) * 2^32.
+    let constants: Vec<u32> = (0..64)
+                                  .map(|i| ((i as f64 + 1.0).sin().abs() * 4294967296.0) as u32)
+                                  .collect();
+
+    let mut message = data.to_owned();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    let bits = (data.len() as u64).wrapping_mul(8);
+    for i in 0..8 {
+        message.push((bits >> (8 * i)) as u8);
+    }
+
+    let mut state: [u32; 4] = [0x67452301, 0xefcdab89, 0x98badcfe, 0x10325476];
+    for chunk in message.chunks(64) {
+        let mut words = [0u32; 16];
+        for (i, word) in words.iter_mut().enumerate() {
+            *word = (chunk[4 * i] as u32) | (chunk[4 * i + 1] as u32) << 8 |
+                    (chunk[4 * i + 2] as u32) << 16 |
+                    (chunk[4 * i + 3] as u32) << 24;
+        }
+
+        let (mut a, mut b, mut c, mut d) = (state[0], state[1], state[2], state[3]);
+        for i in 0..64 {
+            let (f, g) = match i / 16 {
+                0 => ((b & c) | (!b & d), i),
+                1 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+                2 => (b ^ c ^ d, (3 * i + 5) % 16),
+                _ => (c ^ (b | !d), (7 * i) % 16),
+            };
+            let rotated = a.wrapping_add(f)
+                           .wrapping_add(constants[i])
+                           .wrapping_add(words[g])
+                           .rotate_left(SHIFTS[i]);
+            a = d;
+            d = c;
+            c = b;
+            b = b.wrapping_add(rotated);
+        }
+
+        state = [state[0].wrapping_add(a),
+                 state[1].wrapping_add(b),
+                 state[2].wrapping_add(c),
+                 state[3].wrapping_add(d)];
+    }
+
+    let mut digest = String::new();
+    for word in &state {
+        for i in 0..4 {
+            digest.push_str(&format!("{:02x}", (word >> (8 * i)) as u8));
+        }
+    }
+    digest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The test suite of RFC 1321.
+    #[test]
+    fn test_suite() {
+        let suite = [("", "d41d8cd98f00b204e9800998ecf8427e"),
+                     ("a", "0cc175b9c0f1b6a831c399e269772661"),
+                     ("abc", "900150983cd24fb0d6963f7d28e17f72"),
+                     ("message digest", "f96b697d7cb7938d525a2f31aaf161d0"),
+                     ("abcdefghijklmnopqrstuvwxyz", "c3fcd3d76192e4007dfb496cca67e13b"),
+                     ("ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789",
+                      "d174ab98d277d9f5a5611c2c9f419d9f"),
+                     ("1234567890123456789012345678901234567890123456789012345678901234567890\
+                       1234567890",
+                      "57edf4a22be3c955ac49da2e2107b67a")];
+        for &(message, digest) in &suite {
+            assert_eq!(hex_digest(message.as_bytes()), digest, "{:?}", message);
+        }
+    }
+}
@@ -0,0 +1,592 @@
+use std::env;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use rustc_serialize::Decodable;
+use rustc_serialize::json::Json;
+
+use {CiEnvironment, Manifest, Toolchain};
+use cargo;
+use error::{Error, Result};
+use utils;
+
+pub mod codecov;
+pub mod coveralls;
+mod kcov;
+mod llvm;
+mod md5;
+pub mod patch;
+pub mod report;
+mod sha256;
+pub mod threshold;
+
+use self::patch::PatchCoverage;
+use self::report::{Format, Report};
+use self::threshold::Thresholds;
+
+#[derive(Debug, Clone, Copy, RustcDecodable)]
+enum Backend {
+    Kcov,
+    Llvm,
+}
+
+impl Backend {
+    fn default_merge_dir(&self, manifest: &Manifest) -> PathBuf {
+        match *self {
+            Backend::Kcov => manifest.target_directory().join("kcov"),
+            Backend::Llvm => manifest.target_directory().join("llvm-cov"),
+        }
+    }
+}
+
+/// The options that `coverage`, `coveralls` and `codecov` share, which are decoded into
+/// `SharedArgs`.
+macro_rules! shared_options {
+    () => {
+r#"
+    -m DIR, --merge-into DIR
+                          the directory to put the final merged result into (default `target/kcov`,
+                          or `target/llvm-cov` with the llvm backend)
+
+    --backend BACKEND     how to record coverage. `kcov` runs each test binary under kcov. `llvm`
+                          builds the tests with `-C instrument-coverage`, and merges the results
+                          with llvm-profdata and llvm-cov; this requires the `llvm-tools-preview`
+                          rustup component, and also records doc tests on nightly.
+                          [default: kcov]
+
+    --fail-under-lines PCT
+                          fail if less than PCT percent of the lines are covered
+
+    --fail-under-functions PCT
+                          fail if less than PCT percent of the functions are covered (only the
+                          llvm backend records functions)
+
+    --kcov PATH           the kcov to run. Otherwise it is taken from `$KCOV`, the `PATH` or
+                          `~/.cache/travis-cargo`, where a pinned release of kcov is built if it
+                          can't be found
+
+    --kcov-source TARBALL
+                          build kcov from this source archive of the pinned release, instead of
+                          downloading it
+
+    --no-fail-fast        record every test binary and merge their coverage even if some fail, then
+                          list the failures, write the report, and exit unsuccessfully
+
+    -j N, --jobs N        record this many test binaries at once with kcov. Their output is
+                          printed as each one finishes. Defaults to the number of CPUs
+
+    --no-sudo             don't use `sudo` to install kcov's deps. Requires that
+                          libcurl4-openssl-dev, libelf-dev and libdw-dev are installed (e.g., via
+                          `addons: apt: packages:`)
+
+    --verify              pass `--verify` to kcov, to avoid some crashes. See
+                          <https://github.com/huonw/travis-cargo/issues/12>. This requires
+                          installing the `binutils-dev` package.
+
+    --exclude-pattern PATTERN
+                          pass additional comma-separated exclusionary patterns to kcov. See
+                          <https://github.com/SimonKagstrom/kcov#filtering-output> for how patterns
+                          work. By default, the /.cargo pattern is ignored. Example:
+                          `--exclude-pattern="test/,bench/"`
+
+    --kcov-options OPTION ...
+                         pass additional arguments to kcov, apart from `--verify` and
+                         `--exclude-pattern`, when recording coverage. Specify multiple times for
+                         multiple arguments. Example: --kcov-options="--debug=31"
+"#
+    }
+}
+
+/// The note on the minimums that can be configured, for the end of a usage.
+macro_rules! minimums {
+    () => {
+r#"
+Minimums for the whole report and for single files can also be given in Cargo.toml, under
+`[package.metadata.travis-cargo.coverage]`:
+
+    fail-under-lines = 80
+    fail-under-functions = 75
+
+    [package.metadata.travis-cargo.coverage.files]
+    "src/lib.rs" = 90
+    "src/parser.rs" = { lines = 95, functions = 100 }
+"#
+    }
+}
+
+const COVERAGE_USAGE: &'static str = concat!(r#"
+Usage:
+    travis-cargo coverage [options] [--] [<args>...]
+
+Record coverage of `cargo test`, this runs all binaries that `cargo test` runs but not doc tests
+(unless recording with the llvm backend on nightly). The results of all tests are merged into a
+single directory.
+
+positional arguments:
+    args                  arguments to pass to `cargo test`. Those after a second `--` are passed to
+                          each test binary, like `cargo test -- <args>`
+
+optional arguments:
+    -h, --help            show this help message and exit
+
+    --format FORMAT       also write the merged results as `lcov`, `cobertura` (XML) or
+                          `json-summary`
+
+    --output FILE         the file to write the `--format` results to (default `lcov.info`,
+                          `cobertura.xml` or `coverage-summary.json` in the merge directory)
+
+    --diff-base REF       also print the coverage of the lines that changed since the branch forked
+                          from REF (e.g. `origin/master`), according to `git diff`
+
+    --fail-under-patch PCT
+                          fail if less than PCT percent of the changed lines are covered. Requires
+                          `--diff-base`
+"#,
+                                             shared_options!(),
+                                             minimums!(),
+                                             r#"
+The minimum for the changed lines can be given there too, as `fail-under-patch = 90`.
+"#);
+
+#[derive(Debug, RustcDecodable)]
+struct CoverageArgs {
+    flag_format: Option<String>,
+    flag_output: Option<String>,
+    flag_diff_base: Option<String>,
+    flag_fail_under_patch: Option<f64>,
+}
+
+const COVERALLS_USAGE: &'static str = concat!(r#"
+Usage:
+    travis-cargo coveralls [options] [--] [<args>...]
+    travis-cargo coveralls --finish [--endpoint URL]
+
+Record coverage of `cargo test` like `travis-cargo coverage`, and upload it to coveralls.io. The
+merged results can be accessed in `target/kcov` (or `target/llvm-cov`).
+
+Public repositories on Travis CI are identified by the job id. Everywhere else, set
+`COVERALLS_REPO_TOKEN` to the repository's token from coveralls.io.
+
+positional arguments:
+    args                  arguments to pass to `cargo test`. Those after a second `--` are passed to
+                          each test binary, like `cargo test -- <args>`
+
+optional arguments:
+    -h, --help            show this help message and exit
+
+    --from DIR            upload the coverage that was already merged into DIR, e.g. `target/kcov`
+                          by `travis-cargo coverage`, instead of recording it. The options for
+                          recording and `args` are ignored
+
+    --endpoint URL        the coveralls instance to upload to [default: https://coveralls.io]
+
+    --parallel            upload as one of several parallel jobs of the build (also enabled by
+                          `COVERALLS_PARALLEL=true`). coveralls.io combines their results once
+                          `travis-cargo coveralls --finish` runs after all of them
+
+    --finish              tell coveralls.io that all of the parallel jobs of the build have been
+                          uploaded, instead of recording coverage
+"#,
+                                              shared_options!(),
+                                              minimums!());
+
+#[derive(Debug, RustcDecodable)]
+struct CoverallsArgs {
+    flag_endpoint: String,
+    flag_parallel: bool,
+    flag_finish: bool,
+}
+
+const CODECOV_USAGE: &'static str = concat!(r#"
+Usage:
+    travis-cargo codecov [options] [--] [<args>...]
+
+Record coverage of `cargo test` like `travis-cargo coverage`, and upload it to codecov.io. The
+merged results can be accessed in `target/kcov` (or `target/llvm-cov`).
+
+Public repositories on Travis CI, AppVeyor, GitHub Actions and Azure Pipelines don't need a token.
+Everywhere else, set `CODECOV_TOKEN` to the repository's upload token from codecov.io.
+
+positional arguments:
+    args                  arguments to pass to `cargo test`. Those after a second `--` are passed to
+                          each test binary, like `cargo test -- <args>`
+
+optional arguments:
+    -h, --help            show this help message and exit
+
+    --from DIR            upload the coverage that was already merged into DIR, e.g. `target/kcov`
+                          by `travis-cargo coverage`, instead of recording it. The options for
+                          recording and `args` are ignored
+
+    --url URL             the Codecov instance to upload to [default: https://codecov.io]
+
+    --flags FLAGS         comma-separated flags to group the report under, e.g. `unittests`
+
+    --name NAME           a name for the upload
+"#,
+                                            shared_options!(),
+                                            minimums!());
+
+#[derive(Debug, RustcDecodable)]
+struct CodecovArgs {
+    flag_url: String,
+    flag_flags: Option<String>,
+    flag_name: Option<String>,
+}
+
+/// The options of `shared_options!`, and `--from`, which `coverage` doesn't have.
+#[derive(Debug, RustcDecodable)]
+struct SharedArgs {
+    flag_from: Option<String>,
+    flag_merge_into: Option<String>,
+    flag_backend: Backend,
+    flag_fail_under_lines: Option<f64>,
+    flag_fail_under_functions: Option<f64>,
+    flag_kcov: Option<String>,
+    flag_kcov_source: Option<String>,
+    flag_jobs: Option<usize>,
+    flag_no_fail_fast: bool,
+    flag_no_sudo: bool,
+    flag_verify: bool,
+    arg_args: Vec<String>,
+    flag_kcov_options: Vec<String>,
+    flag_exclude_pattern: Option<String>,
+}
+
+/// Runs `travis-cargo coverage`, whose command line is `argv`.
+pub fn coverage(manifest: &Manifest, toolchain: &Toolchain, argv: &[String]) -> Result<()> {
+    let (argv, rest) = split_separator(argv);
+    let (args, shared): (CoverageArgs, SharedArgs) = match try!(parse_args(COVERAGE_USAGE, argv)) {
+        Some(args) => args,
+        None => return Ok(()),
+    };
+
+    let format = match args.flag_format {
+        Some(ref format) => Some(try!(Format::parse(format))),
+        None => None,
+    };
+    if args.flag_fail_under_patch.is_some() && args.flag_diff_base.is_none() {
+        return Err(Error::InvalidArgument("--fail-under-patch requires --diff-base".to_owned()));
+    }
+    let thresholds = try!(Thresholds::new(manifest.config(),
+                                          shared.flag_fail_under_lines,
+                                          shared.flag_fail_under_functions,
+                                          args.flag_fail_under_patch));
+
+    let (report, merge_dir, tested) = try!(report(manifest, toolchain, shared, rest));
+
+    let patch = match args.flag_diff_base {
+        Some(ref base) => Some(try!(PatchCoverage::from_git(base, &report))),
+        None => None,
+    };
+    if let Some(ref patch) = patch {
+        patch.print();
+    }
+
+    if let Some(format) = format {
+        let output = args.flag_output
+                         .map(PathBuf::from)
+                         .unwrap_or(merge_dir.join(format.default_file_name()));
+
+        println!("Writing {}", output.display());
+        let mut file = try!(File::create(output));
+        try!(report.write(format, &mut file, manifest.workspace_root()));
+    }
+
+    // Everything above is done even if tests failed, so that their coverage can be looked at.
+    let checked = thresholds.check(&report, patch.as_ref(), manifest.workspace_root());
+    try!(tested);
+    checked
+}
+
+/// Runs `travis-cargo coveralls`, whose command line is `argv`.
+pub fn coveralls(manifest: &Manifest,
+                 ci: &dyn CiEnvironment,
+                 toolchain: &Toolchain,
+                 argv: &[String])
+                 -> Result<()> {
+    let (argv, rest) = split_separator(argv);
+    let parsed = try!(parse_args(COVERALLS_USAGE, argv));
+    let (args, shared): (CoverallsArgs, SharedArgs) = match parsed {
+        Some(args) => args,
+        None => return Ok(()),
+    };
+
+    let repo_token = env::var("COVERALLS_REPO_TOKEN").ok().and_then(|token| {
+        if token.is_empty() { None } else { Some(token) }
+    });
+    let repo_token = repo_token.as_ref().map(|token| &token[..]);
+    if args.flag_finish {
+        return coveralls::finish(&args.flag_endpoint, ci, repo_token);
+    }
+    // coveralls.io only identifies public repositories by the job id on Travis CI.
+    if (ci.name() != "travis-ci" || ci.job_id().is_none()) && repo_token.is_none() {
+        return Err(Error::MissingEnvVar("COVERALLS_REPO_TOKEN".to_owned()));
+    }
+    let parallel = args.flag_parallel ||
+                   env::var("COVERALLS_PARALLEL").map(|value| value == "true").unwrap_or(false);
+
+    let thresholds = try!(Thresholds::new(manifest.config(),
+                                          shared.flag_fail_under_lines,
+                                          shared.flag_fail_under_functions,
+                                          None));
+    let (report, _, tested) = try!(report(manifest, toolchain, shared, rest));
+
+    let job = try!(coveralls::job(&report, ci, manifest.workspace_root(), repo_token, parallel));
+    println!("Uploading coverage to {}", args.flag_endpoint);
+    try!(coveralls::upload(&args.flag_endpoint, &job));
+
+    // The results are uploaded either way, so that the drop shows up on coveralls.io too.
+    let checked = thresholds.check(&report, None, manifest.workspace_root());
+    try!(tested);
+    checked
+}
+
+/// Runs `travis-cargo codecov`, whose command line is `argv`.
+pub fn codecov(manifest: &Manifest,
+               ci: &dyn CiEnvironment,
+               toolchain: &Toolchain,
+               argv: &[String])
+               -> Result<()> {
+    let (argv, rest) = split_separator(argv);
+    let (args, shared): (CodecovArgs, SharedArgs) = match try!(parse_args(CODECOV_USAGE, argv)) {
+        Some(args) => args,
+        None => return Ok(()),
+    };
+
+    let upload = codecov::Upload {
+        token: env::var("CODECOV_TOKEN").ok().and_then(|token| {
+            if token.is_empty() { None } else { Some(token) }
+        }),
+        flags: args.flag_flags
+                   .iter()
+                   .flat_map(|flags| flags.split(','))
+                   .map(str::trim)
+                   .filter(|flag| !flag.is_empty())
+                   .map(String::from)
+                   .collect(),
+        name: args.flag_name,
+    };
+    let query = try!(codecov::query(ci, &upload));
+    let thresholds = try!(Thresholds::new(manifest.config(),
+                                          shared.flag_fail_under_lines,
+                                          shared.flag_fail_under_functions,
+                                          None));
+    let (report, merge_dir, tested) = try!(report(manifest, toolchain, shared, rest));
+
+    let payload = merge_dir.join("codecov.txt");
+    try!(codecov::write_payload(&report,
+                                &mut try!(File::create(&payload)),
+                                manifest.workspace_root()));
+    println!("Uploading coverage to {}", args.flag_url);
+    try!(codecov::upload(&args.flag_url, &query, &payload));
+
+    let checked = thresholds.check(&report, None, manifest.workspace_root());
+    try!(tested);
+    checked
+}
+
+/// Decodes the command line into the options of the subcommand and the shared ones.
+fn parse_args<T: Decodable>(usage: &str, argv: &[String]) -> Result<Option<(T, SharedArgs)>> {
+    match try!(utils::parse_args(usage, argv)) {
+        Some(args) => Ok(try!(utils::parse_args(usage, argv)).map(|shared| (args, shared))),
+        None => Ok(None),
+    }
+}
+
+/// Records the coverage of `cargo test`, or loads what `--from` merged before, returning the
+/// report, the directory it was merged into and whether the tests passed. `rest` is what followed
+/// the first `--`.
+fn report(manifest: &Manifest,
+          toolchain: &Toolchain,
+          shared: SharedArgs,
+          rest: &[String])
+          -> Result<(Report, PathBuf, Result<()>)> {
+    if shared.flag_from.is_some() && shared.flag_merge_into.is_some() {
+        return Err(Error::InvalidArgument("--from and --merge-into can't be used together"
+                                              .to_owned()));
+    }
+    if let Some(from) = shared.flag_from {
+        let from = PathBuf::from(from);
+        return Ok((try!(load_report(&from)), from, Ok(())));
+    }
+
+    let (mut cargo_args, harness_args) = split_harness_args(shared.arg_args, rest);
+    cargo::add_features(&mut cargo_args, toolchain);
+
+    let recording = Recording {
+        test_args: cargo_args,
+        harness_args: harness_args,
+        merge_message: "Merging coverage",
+        merge_dir: shared.flag_merge_into
+                         .map(PathBuf::from)
+                         .unwrap_or(shared.flag_backend.default_merge_dir(manifest)),
+        exclude_pattern: shared.flag_exclude_pattern,
+        no_fail_fast: shared.flag_no_fail_fast,
+    };
+    let kcov_options = kcov::Options {
+        kcov: shared.flag_kcov.map(PathBuf::from),
+        kcov_source: shared.flag_kcov_source.map(PathBuf::from),
+        use_sudo: !shared.flag_no_sudo,
+        verify: shared.flag_verify,
+        jobs: shared.flag_jobs.unwrap_or_else(kcov::default_jobs),
+        extra_args: shared.flag_kcov_options,
+    };
+    let (report, tested) = try!(raw_coverage(manifest,
+                                             toolchain,
+                                             shared.flag_backend,
+                                             &recording,
+                                             &kcov_options));
+    Ok((report, recording.merge_dir, tested))
+}
+
+/// How to record the coverage of `cargo test`, whichever backend does it.
+struct Recording {
+    /// The arguments for `cargo test`.
+    test_args: Vec<String>,
+    /// The arguments for each test binary.
+    harness_args: Vec<String>,
+    merge_message: &'static str,
+    merge_dir: PathBuf,
+    /// More comma-separated patterns of source files to leave out.
+    exclude_pattern: Option<String>,
+    no_fail_fast: bool,
+}
+
+fn raw_coverage(manifest: &Manifest,
+                toolchain: &Toolchain,
+                backend: Backend,
+                recording: &Recording,
+                kcov_options: &kcov::Options)
+                -> Result<(Report, Result<()>)> {
+    let tested = match backend {
+        Backend::Kcov => {
+            let mut cargo = Command::new("cargo");
+            for &(var, value) in &try!(debuginfo_env(manifest)) {
+                println!("Setting {}={}, since kcov needs debuginfo", var, value);
+                cargo.env(var, value);
+            }
+            let test_binaries = try!(test_binaries(cargo, &recording.test_args));
+            try!(kcov::record(manifest, kcov_options, &test_binaries, recording))
+        }
+        Backend::Llvm => try!(llvm::record(manifest, toolchain, recording)),
+    };
+
+    match load_report(&recording.merge_dir) {
+        Ok(report) => Ok((report, tested)),
+        // A failing test explains a missing or empty report better.
+        Err(err) => tested.and(Err(err)),
+    }
+}
+
+fn load_report(merge_dir: &Path) -> Result<Report> {
+    // Without debuginfo, kcov "succeeds" with a report that doesn't cover anything.
+    let report = try!(Report::load(merge_dir));
+    if report.lines().1 == 0 {
+        return Err(Error::EmptyCoverageReport(merge_dir.to_path_buf()));
+    }
+    Ok(report)
+}
+
+/// The profile overrides that turn on the debuginfo of test builds, for the profiles that have it
+/// turned off. `test` inherits the setting of `dev`, which has debuginfo by default.
+fn debuginfo_env(manifest: &Manifest) -> Result<Vec<(&'static str, &'static str)>> {
+    let is_off = |profile: &str, var: &str| -> Result<Option<bool>> {
+        let setting = match env::var(var) {
+            Ok(value) => Some(value),
+            Err(_) => try!(manifest.profile_setting(profile, "debug")),
+        };
+        Ok(setting.map(|setting| ["0", "false", "none"].contains(&setting.trim())))
+    };
+
+    let dev_off = try!(is_off("dev", "CARGO_PROFILE_DEV_DEBUG")).unwrap_or(false);
+    let test_off = try!(is_off("test", "CARGO_PROFILE_TEST_DEBUG")).unwrap_or(dev_off);
+
+    let mut overrides = vec![];
+    if test_off {
+        overrides.push(("CARGO_PROFILE_TEST_DEBUG", "true"));
+    }
+    if dev_off {
+        overrides.push(("CARGO_PROFILE_DEV_DEBUG", "true"));
+    }
+    Ok(overrides)
+}
+
+/// Splits the command line of a subcommand at its first `--`, returning what docopt should parse
+/// and the arguments after the `--`. docopt would take a second `--` right after the first one as
+/// the optional `[--]` of the usage.
+fn split_separator(argv: &[String]) -> (&[String], &[String]) {
+    match argv.iter().skip(2).position(|arg| arg == "--") {
+        Some(i) => (&argv[..i + 2], &argv[i + 3..]),
+        None => (argv, &[]),
+    }
+}
+
+/// Splits the positional arguments of a subcommand and those after its first `--` into those for
+/// `cargo test` and those for the test harness, which follow a second `--`.
+fn split_harness_args(mut args: Vec<String>, rest: &[String]) -> (Vec<String>, Vec<String>) {
+    args.extend(rest.iter().cloned());
+    match args.iter().position(|arg| arg == "--") {
+        Some(i) => {
+            let harness_args = args.split_off(i + 1);
+            args.pop();
+            (args, harness_args)
+        }
+        None => (args, vec![]),
+    }
+}
+
+/// A test executable built by `cargo test`, and the id of the package that it tests.
+struct TestBinary {
+    executable: PathBuf,
+    package_id: String,
+}
+
+// Build the test binaries without running them, and find them via cargo's JSON messages. Unlike
+// the human-readable output, this reports the real path of each executable, including those of
+// workspace members and those under a custom target directory or `--target` triple.
+fn test_binaries(mut cargo: Command, test_args: &[String]) -> Result<Vec<TestBinary>> {
+    let output = try!(utils::run_output(cargo.args(&["test", "--no-run", "--message-format=json"])
+                                             .args(test_args)));
+
+    let mut test_binaries = vec![];
+    for line in output.lines() {
+        let message = match Json::from_str(line) {
+            Ok(message) => message,
+            Err(_) => continue,
+        };
+
+        match message.find("reason").and_then(Json::as_string) {
+            Some("compiler-artifact") => {}
+            Some("compiler-message") => {
+                // Keep the diagnostics visible, since cargo no longer prints them itself.
+                if let Some(rendered) = message.find_path(&["message", "rendered"])
+                                               .and_then(Json::as_string) {
+                    eprint!("{}", rendered);
+                }
+                continue;
+            }
+            _ => continue,
+        }
+
+        let is_test = message.find_path(&["profile", "test"])
+                             .and_then(Json::as_boolean)
+                             .unwrap_or(false);
+        if !is_test {
+            continue;
+        }
+
+        if let Some(executable) = message.find("executable").and_then(Json::as_string) {
+            test_binaries.push(TestBinary {
+                executable: PathBuf::from(executable),
+                package_id: message.find("package_id")
+                                   .and_then(Json::as_string)
+                                   .unwrap_or("")
+                                   .to_owned(),
+            });
+        }
+    }
+
+    Ok(test_binaries)
+}
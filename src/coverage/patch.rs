@@ -0,0 +1,164 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use error::{Error, Result};
+use utils;
+
+use super::report::{percentage, Report};
+
+/// The coverage of the lines that changed relative to a base revision.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PatchCoverage {
+    pub files: BTreeMap<PathBuf, FilePatch>,
+}
+
+/// The changed lines of a file that have code in them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FilePatch {
+    pub covered: Vec<u32>,
+    pub uncovered: Vec<u32>,
+}
+
+impl PatchCoverage {
+    /// Diffs the working tree against where it forked from `base`, e.g. `origin/master`.
+    pub fn from_git(base: &str, report: &Report) -> Result<PatchCoverage> {
+        let toplevel = try!(utils::run_output(Command::new("git")
+                                                  .args(&["rev-parse", "--show-toplevel"])));
+        let merge_base = try!(utils::run_output(Command::new("git")
+                                                    .args(&["merge-base", base, "HEAD"])));
+        let diff = try!(utils::run_output(Command::new("git")
+                                              .args(&["diff",
+                                                      "--unified=0",
+                                                      "--no-color",
+                                                      "--no-ext-diff",
+                                                      "--src-prefix=a/",
+                                                      "--dst-prefix=b/",
+                                                      merge_base.trim()])));
+
+        let changed = try!(changed_lines(&diff));
+        Ok(PatchCoverage::new(report, Path::new(toplevel.trim()), &changed))
+    }
+
+    /// Maps the changed lines of each file, relative to `root`, onto the report.
+    pub fn new(report: &Report,
+               root: &Path,
+               changed: &BTreeMap<PathBuf, Vec<u32>>)
+               -> PatchCoverage {
+        let mut files = BTreeMap::new();
+        for (file, lines) in changed {
+            let coverage = match report.files.get(&root.join(file)) {
+                Some(coverage) => coverage,
+                None => continue,
+            };
+
+            let mut patch = FilePatch::default();
+            for line in lines {
+                match coverage.lines.get(line) {
+                    Some(&0) => patch.uncovered.push(*line),
+                    Some(_) => patch.covered.push(*line),
+                    None => {}
+                }
+            }
+            if !patch.covered.is_empty() || !patch.uncovered.is_empty() {
+                files.insert(file.clone(), patch);
+            }
+        }
+
+        PatchCoverage { files: files }
+    }
+
+    /// The covered and total number of changed lines with code in them.
+    pub fn lines(&self) -> (usize, usize) {
+        self.files.values().fold((0, 0), |(covered, total), file| {
+            (covered + file.covered.len(), total + file.covered.len() + file.uncovered.len())
+        })
+    }
+
+    pub fn print(&self) {
+        let (covered, total) = self.lines();
+        println!("Patch coverage: {} of {} changed lines covered ({:.2}%)",
+                 covered,
+                 total,
+                 percentage(covered, total));
+
+        for (file, patch) in &self.files {
+            let total = patch.covered.len() + patch.uncovered.len();
+            println!("    {}: {} of {} covered ({:.2}%)",
+                     file.display(),
+                     patch.covered.len(),
+                     total,
+                     percentage(patch.covered.len(), total));
+            if !patch.covered.is_empty() {
+                println!("        covered: {}", ranges(&patch.covered));
+            }
+            if !patch.uncovered.is_empty() {
+                println!("        uncovered: {}", ranges(&patch.uncovered));
+            }
+        }
+    }
+}
+
+/// Finds the lines that each file gained in a diff made with `--unified=0`.
+pub fn changed_lines(diff: &str) -> Result<BTreeMap<PathBuf, Vec<u32>>> {
+    let mut changed = BTreeMap::new();
+    let mut file = None;
+
+    for line in diff.lines() {
+        if line.starts_with("+++ ") {
+            // deleted files are diffed against `/dev/null`.
+            file = if line.starts_with("+++ b/") {
+                Some(PathBuf::from(&line["+++ b/".len()..]))
+            } else {
+                None
+            };
+        } else if line.starts_with("@@ ") {
+            let file = match file {
+                Some(ref file) => file,
+                None => continue,
+            };
+
+            // `@@ -start,count +start,count @@`, where a missing count is 1.
+            let added = try!(line.split(' ')
+                                 .find(|range| range.starts_with('+'))
+                                 .ok_or_else(|| diff_parse(line)));
+            let mut range = added[1..].splitn(2, ',');
+            let start: u32 = try!(range.next().unwrap().parse().map_err(|_| diff_parse(line)));
+            let count: u32 = match range.next() {
+                Some(count) => try!(count.parse().map_err(|_| diff_parse(line))),
+                None => 1,
+            };
+
+            changed.entry(file.clone())
+                   .or_insert_with(Vec::new)
+                   .extend(start..start + count);
+        }
+    }
+
+    Ok(changed)
+}
+
+fn diff_parse(line: &str) -> Error {
+    Error::ReportParse(format!("unexpected hunk header in `git diff`: {}", line))
+}
+
+/// Formats sorted line numbers as `1-3, 7, 9-10`.
+fn ranges(lines: &[u32]) -> String {
+    let mut ranges: Vec<(u32, u32)> = vec![];
+    for &line in lines {
+        if let Some(last) = ranges.last_mut() {
+            if last.1 + 1 == line {
+                last.1 = line;
+                continue;
+            }
+        }
+        ranges.push((line, line));
+    }
+
+    ranges.iter()
+          .map(|&(start, end)| {
+              if start == end { start.to_string() } else { format!("{}-{}", start, end) }
+          })
+          .collect::<Vec<_>>()
+          .join(", ")
+}
@@ -0,0 +1,398 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rustc_serialize::json::{self, Json};
+
+use error::{Error, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Lcov,
+    Cobertura,
+    JsonSummary,
+}
+
+impl Format {
+    pub fn parse(s: &str) -> Result<Format> {
+        match s {
+            "lcov" => Ok(Format::Lcov),
+            "cobertura" => Ok(Format::Cobertura),
+            "json-summary" => Ok(Format::JsonSummary),
+            _ => {
+                Err(Error::InvalidArgument(format!("unknown coverage format `{}`, expected \
+                                                    `lcov`, `cobertura` or `json-summary`",
+                                                   s)))
+            }
+        }
+    }
+
+    pub fn default_file_name(&self) -> &'static str {
+        match *self {
+            Format::Lcov => "lcov.info",
+            Format::Cobertura => "cobertura.xml",
+            Format::JsonSummary => "coverage-summary.json",
+        }
+    }
+}
+
+/// Merged coverage of a test run, as recorded by either backend.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Report {
+    pub files: BTreeMap<PathBuf, FileCoverage>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FileCoverage {
+    /// The number of hits for each instrumented line.
+    pub lines: BTreeMap<u32, u64>,
+    /// Only the llvm backend records functions.
+    pub functions: Vec<Function>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    pub name: String,
+    pub line: u32,
+    pub hits: u64,
+}
+
+impl FileCoverage {
+    pub fn lines_covered(&self) -> usize {
+        self.lines.values().filter(|&&hits| hits > 0).count()
+    }
+
+    pub fn functions_covered(&self) -> usize {
+        self.functions.iter().filter(|function| function.hits > 0).count()
+    }
+}
+
+impl Report {
+    /// Loads the report from a directory that a backend merged its results into.
+    pub fn load(merge_dir: &Path) -> Result<Report> {
+        let lcov = merge_dir.join("lcov.info");
+        let cobertura = merge_dir.join("kcov-merged").join("cobertura.xml");
+
+        if lcov.is_file() {
+            Report::from_lcov(BufReader::new(try!(File::open(lcov))))
+        } else if cobertura.is_file() {
+            let mut xml = String::new();
+            try!(try!(File::open(cobertura)).read_to_string(&mut xml));
+            Report::from_cobertura(&xml)
+        } else {
+            Err(Error::NoCoverageReport(merge_dir.to_owned()))
+        }
+    }
+
+    /// Parses a tracefile in the format of `lcov`, as exported by `llvm-cov export`.
+    pub fn from_lcov<R: BufRead>(lcov: R) -> Result<Report> {
+        let mut report = Report::default();
+        let mut current: Option<(PathBuf, FileCoverage)> = None;
+        // How many `FNDA`s of each function name the current file has had. Functions can share a
+        // name, and the nth `FNDA` of a name is for the nth `FN` of it, like `write_lcov` does.
+        let mut fnda_counts: BTreeMap<String, usize> = BTreeMap::new();
+
+        for line in lcov.lines() {
+            let line = try!(line);
+            let line = line.trim();
+            let (key, value) = match line.find(':') {
+                Some(i) => (&line[..i], &line[i + 1..]),
+                None => (line, ""),
+            };
+
+            match key {
+                "SF" => {
+                    current = Some((PathBuf::from(value), FileCoverage::default()));
+                    fnda_counts.clear();
+                }
+                "end_of_record" => {
+                    if let Some((path, file)) = current.take() {
+                        report.merge_file(path, file);
+                    }
+                }
+                "DA" | "FN" | "FNDA" => {
+                    let file = match current {
+                        Some((_, ref mut file)) => file,
+                        None => return Err(report_parse(format!("`{}` outside of a file", key))),
+                    };
+                    // function names can contain commas, `DA` has an optional checksum.
+                    let mut fields = value.splitn(if key == "DA" { 3 } else { 2 }, ',');
+                    let first = fields.next().unwrap_or("");
+                    let second = fields.next().unwrap_or("");
+
+                    match key {
+                        "DA" => {
+                            let line = try!(parse_number(first));
+                            let hits = try!(parse_number(second));
+                            *file.lines.entry(line as u32).or_insert(0) += hits;
+                        }
+                        "FN" => {
+                            file.functions.push(Function {
+                                name: second.to_owned(),
+                                line: try!(parse_number(first)) as u32,
+                                hits: 0,
+                            });
+                        }
+                        _ => {
+                            let hits = try!(parse_number(first));
+                            let count = fnda_counts.entry(second.to_owned()).or_insert(0);
+                            if let Some(function) = file.functions
+                                                        .iter_mut()
+                                                        .filter(|function| function.name == second)
+                                                        .nth(*count) {
+                                function.hits += hits;
+                            }
+                            *count += 1;
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Parses a Cobertura XML report, as written by kcov.
+    pub fn from_cobertura(xml: &str) -> Result<Report> {
+        let mut report = Report::default();
+        let mut source = PathBuf::new();
+        let mut current: Option<(PathBuf, FileCoverage)> = None;
+
+        let mut rest = xml;
+        while let Some(start) = rest.find('<') {
+            rest = &rest[start + 1..];
+            let end = try!(rest.find('>').ok_or_else(|| report_parse("unterminated tag")));
+            let tag = &rest[..end];
+            rest = &rest[end + 1..];
+
+            if tag == "source" {
+                let end = try!(rest.find('<').ok_or_else(|| report_parse("unterminated source")));
+                source = PathBuf::from(unescape(rest[..end].trim()));
+            } else if tag.starts_with("class ") {
+                if let Some((path, file)) = current.take() {
+                    report.merge_file(path, file);
+                }
+                let filename = try!(attribute(tag, "filename")
+                                        .ok_or_else(|| report_parse("class without a filename")));
+                current = Some((source.join(filename), FileCoverage::default()));
+            } else if tag.starts_with("/class") {
+                if let Some((path, file)) = current.take() {
+                    report.merge_file(path, file);
+                }
+            } else if tag.starts_with("line ") {
+                if let Some((_, ref mut file)) = current {
+                    let number = attribute(tag, "number").unwrap_or_default();
+                    let hits = attribute(tag, "hits").unwrap_or_default();
+                    *file.lines.entry(try!(parse_number(&number)) as u32).or_insert(0) +=
+                        try!(parse_number(&hits));
+                }
+            }
+        }
+
+        Ok(report)
+    }
+
+    fn merge_file(&mut self, path: PathBuf, file: FileCoverage) {
+        let merged = self.files.entry(path).or_insert_with(FileCoverage::default);
+        for (line, hits) in file.lines {
+            *merged.lines.entry(line).or_insert(0) += hits;
+        }
+        merged.functions.extend(file.functions);
+    }
+
+    /// The number of covered and of instrumented lines.
+    pub fn lines(&self) -> (usize, usize) {
+        self.files.values().fold((0, 0), |(covered, total), file| {
+            (covered + file.lines_covered(), total + file.lines.len())
+        })
+    }
+
+    /// The number of covered and of instrumented functions.
+    pub fn functions(&self) -> (usize, usize) {
+        self.files.values().fold((0, 0), |(covered, total), file| {
+            (covered + file.functions_covered(), total + file.functions.len())
+        })
+    }
+
+    /// Writes the report in the given format. Formats that support it name files under
+    /// `source_root` relative to it.
+    pub fn write<W: Write>(&self,
+                           format: Format,
+                           out: &mut W,
+                           source_root: &Path)
+                           -> io::Result<()> {
+        match format {
+            Format::Lcov => self.write_lcov(out),
+            Format::Cobertura => self.write_cobertura(out, source_root),
+            Format::JsonSummary => self.write_json_summary(out),
+        }
+    }
+
+    pub fn write_lcov<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        for (path, file) in &self.files {
+            try!(writeln!(out, "SF:{}", path.display()));
+            for function in &file.functions {
+                try!(writeln!(out, "FN:{},{}", function.line, function.name));
+            }
+            for function in &file.functions {
+                try!(writeln!(out, "FNDA:{},{}", function.hits, function.name));
+            }
+            try!(writeln!(out, "FNF:{}", file.functions.len()));
+            try!(writeln!(out, "FNH:{}", file.functions_covered()));
+            for (line, hits) in &file.lines {
+                try!(writeln!(out, "DA:{},{}", line, hits));
+            }
+            try!(writeln!(out, "LF:{}", file.lines.len()));
+            try!(writeln!(out, "LH:{}", file.lines_covered()));
+            try!(writeln!(out, "end_of_record"));
+        }
+        Ok(())
+    }
+
+    /// Writes a Cobertura XML report. Files under `source_root` are named relative to it.
+    pub fn write_cobertura<W: Write>(&self, out: &mut W, source_root: &Path) -> io::Result<()> {
+        let (covered, total) = self.lines();
+        let timestamp = SystemTime::now()
+                            .duration_since(UNIX_EPOCH)
+                            .map(|duration| duration.as_secs())
+                            .unwrap_or(0);
+
+        try!(writeln!(out, r#"<?xml version="1.0" ?>"#));
+        try!(writeln!(out,
+                      concat!(r#"<!DOCTYPE coverage SYSTEM "#,
+                              r#""http://cobertura.sourceforge.net/xml/coverage-04.dtd">"#)));
+        try!(writeln!(out,
+                      concat!(r#"<coverage line-rate="{}" lines-covered="{}" lines-valid="{}" "#,
+                              r#"branch-rate="0" branches-covered="0" branches-valid="0" "#,
+                              r#"complexity="0" version="1.9" timestamp="{}">"#),
+                      rate(covered, total),
+                      covered,
+                      total,
+                      timestamp));
+        try!(writeln!(out, "  <sources>"));
+        try!(writeln!(out, "    <source>{}</source>", escape(&source_root.display().to_string())));
+        try!(writeln!(out, "  </sources>"));
+        try!(writeln!(out, "  <packages>"));
+        try!(writeln!(out,
+                      r#"    <package name="" line-rate="{}" branch-rate="0" complexity="0">"#,
+                      rate(covered, total)));
+        try!(writeln!(out, "      <classes>"));
+        for (path, file) in &self.files {
+            let filename = path.strip_prefix(source_root).unwrap_or(path).display().to_string();
+            try!(writeln!(out,
+                          concat!(r#"        <class name="{0}" filename="{0}" line-rate="{1}" "#,
+                                  r#"branch-rate="0" complexity="0">"#),
+                          escape(&filename),
+                          rate(file.lines_covered(), file.lines.len())));
+            try!(writeln!(out, "          <methods>"));
+            for function in &file.functions {
+                try!(writeln!(out,
+                              concat!(r#"            <method name="{}" signature="" "#,
+                                      r#"line-rate="{}" branch-rate="0" complexity="0">"#),
+                              escape(&function.name),
+                              rate(if function.hits > 0 { 1 } else { 0 }, 1)));
+                try!(writeln!(out, "              <lines>"));
+                try!(writeln!(out,
+                              r#"                <line number="{}" hits="{}" branch="false"/>"#,
+                              function.line,
+                              function.hits));
+                try!(writeln!(out, "              </lines>"));
+                try!(writeln!(out, "            </method>"));
+            }
+            try!(writeln!(out, "          </methods>"));
+            try!(writeln!(out, "          <lines>"));
+            for (line, hits) in &file.lines {
+                try!(writeln!(out,
+                              r#"            <line number="{}" hits="{}" branch="false"/>"#,
+                              line,
+                              hits));
+            }
+            try!(writeln!(out, "          </lines>"));
+            try!(writeln!(out, "        </class>"));
+        }
+        try!(writeln!(out, "      </classes>"));
+        try!(writeln!(out, "    </package>"));
+        try!(writeln!(out, "  </packages>"));
+        writeln!(out, "</coverage>")
+    }
+
+    /// Writes a summary in the format of istanbul's `json-summary` reporter: the line and
+    /// function totals of the whole report under `total`, and of each file under its path.
+    pub fn write_json_summary<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let mut summary = BTreeMap::new();
+        summary.insert("total".to_owned(), summary_json(self.lines(), self.functions()));
+        for (path, file) in &self.files {
+            summary.insert(path.display().to_string(),
+                           summary_json((file.lines_covered(), file.lines.len()),
+                                        (file.functions_covered(), file.functions.len())));
+        }
+
+        writeln!(out, "{}", json::as_pretty_json(&Json::Object(summary)))
+    }
+}
+
+fn summary_json(lines: (usize, usize), functions: (usize, usize)) -> Json {
+    let counts = |(covered, total): (usize, usize)| {
+        let mut counts = BTreeMap::new();
+        counts.insert("total".to_owned(), Json::U64(total as u64));
+        counts.insert("covered".to_owned(), Json::U64(covered as u64));
+        counts.insert("skipped".to_owned(), Json::U64(0));
+        counts.insert("pct".to_owned(), Json::F64(percentage(covered, total)));
+        Json::Object(counts)
+    };
+
+    let mut summary = BTreeMap::new();
+    summary.insert("lines".to_owned(), counts(lines));
+    summary.insert("functions".to_owned(), counts(functions));
+    Json::Object(summary)
+}
+
+/// The percentage of covered items, rounded to two decimal places. Nothing to cover counts as
+/// fully covered.
+pub fn percentage(covered: usize, total: usize) -> f64 {
+    if total == 0 {
+        100.0
+    } else {
+        (covered as f64 * 10000.0 / total as f64).round() / 100.0
+    }
+}
+
+fn rate(covered: usize, total: usize) -> String {
+    format!("{:.4}", percentage(covered, total) / 100.0)
+}
+
+fn parse_number(s: &str) -> Result<u64> {
+    s.trim().parse().map_err(|_| report_parse(format!("invalid number `{}`", s)))
+}
+
+fn report_parse<S: Into<String>>(message: S) -> Error {
+    Error::ReportParse(message.into())
+}
+
+/// Reads the value of an attribute from the inside of an XML tag.
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    let needle = format!(" {}=\"", name);
+    tag.find(&needle).and_then(|start| {
+        let value = &tag[start + needle.len()..];
+        value.find('"').map(|end| unescape(&value[..end]))
+    })
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+     .replace('<', "&lt;")
+     .replace('>', "&gt;")
+     .replace('"', "&quot;")
+}
+
+fn unescape(s: &str) -> String {
+    s.replace("&lt;", "<")
+     .replace("&gt;", ">")
+     .replace("&quot;", "\"")
+     .replace("&apos;", "'")
+     .replace("&amp;", "&")
+}
@@ -0,0 +1,101 @@
+//! SHA-256, which the source archive of kcov is verified with.
+
+// the first 32 bits of the fractional parts of the cube roots of the first 64 primes.
+const CONSTANTS: [u32; 64] = [0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b,
+                              0x59f111f1, 0x923f82a4, 0xab1c5ed5, 0xd807aa98, 0x12835b01,
+                              0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7,
+                              0xc19bf174, 0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc,
+                              0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da, 0x983e5152,
+                              0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147,
+                              0x06ca6351, 0x14292967, 0x27b70a85, 0x2e1b2138, 0x4d2c6dfc,
+                              0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+                              0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819,
+                              0xd6990624, 0xf40e3585, 0x106aa070, 0x19a4c116, 0x1e376c08,
+                              0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f,
+                              0x682e6ff3, 0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208,
+                              0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2];
+
+/// The lowercase hex digest of `data`.
+pub fn hex_digest(data: &[u8]) -> String {
+    let mut message = data.to_owned();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    let bits = (data.len() as u64).wrapping_mul(8);
+    for i in (0..8).rev() {
+        message.push((bits >> (8 * i)) as u8);
+    }
+
+    let mut state: [u32; 8] = [0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f,
+                               0x9b05688c, 0x1f83d9ab, 0x5be0cd19];
+    for chunk in message.chunks(64) {
+        let mut words = [0u32; 64];
+        for (i, word) in words.iter_mut().take(16).enumerate() {
+            *word = (chunk[4 * i] as u32) << 24 | (chunk[4 * i + 1] as u32) << 16 |
+                    (chunk[4 * i + 2] as u32) << 8 |
+                    (chunk[4 * i + 3] as u32);
+        }
+        for i in 16..64 {
+            let s0 = words[i - 15].rotate_right(7) ^ words[i - 15].rotate_right(18) ^
+                     (words[i - 15] >> 3);
+            let s1 = words[i - 2].rotate_right(17) ^ words[i - 2].rotate_right(19) ^
+                     (words[i - 2] >> 10);
+            words[i] = words[i - 16].wrapping_add(s0).wrapping_add(words[i - 7]).wrapping_add(s1);
+        }
+
+        let (mut a, mut b, mut c, mut d) = (state[0], state[1], state[2], state[3]);
+        let (mut e, mut f, mut g, mut h) = (state[4], state[5], state[6], state[7]);
+        for (constant, word) in CONSTANTS.iter().zip(words.iter()) {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let choice = (e & f) ^ (!e & g);
+            let temp1 = h.wrapping_add(s1)
+                         .wrapping_add(choice)
+                         .wrapping_add(*constant)
+                         .wrapping_add(*word);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let majority = (a & b) ^ (a & c) ^ (b & c);
+            let temp2 = s0.wrapping_add(majority);
+
+            h = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(temp1);
+            d = c;
+            c = b;
+            b = a;
+            a = temp1.wrapping_add(temp2);
+        }
+
+        for (word, value) in state.iter_mut().zip(&[a, b, c, d, e, f, g, h]) {
+            *word = word.wrapping_add(*value);
+        }
+    }
+
+    state.iter().map(|word| format!("{:08x}", word)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The test vectors of FIPS 180-2.
+    #[test]
+    fn test_vectors() {
+        assert_eq!(hex_digest(b""),
+                   "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+        assert_eq!(hex_digest(b"abc"),
+                   "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+        assert_eq!(hex_digest(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"),
+                   "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1");
+        assert_eq!(hex_digest(b"abcdefghbcdefghicdefghijdefghijkefghijklfghijklmghijklmn\
+                                hijklmnoijklmnopjklmnopqklmnopqrlmnopqrsmnopqrstnopqrstu"),
+                   "cf5b16a778af8380036ce59e7b0492370b249b11e8f07a51afac45037afee9d1");
+    }
+
+    #[test]
+    fn million_as() {
+        assert_eq!(hex_digest(&vec![b'a'; 1_000_000]),
+                   "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0");
+    }
+}
@@ -0,0 +1,210 @@
+use std::path::{Path, PathBuf};
+
+use rustc_serialize::json::Json;
+
+use error::{Error, Result};
+
+use super::patch::PatchCoverage;
+use super::report::{percentage, Report};
+
+/// The minimum coverage that a report has to reach, in percent.
+#[derive(Debug, Clone, Default)]
+pub struct Thresholds {
+    pub lines: Option<f64>,
+    pub functions: Option<f64>,
+    /// For the lines changed relative to `--diff-base`.
+    pub patch: Option<f64>,
+    /// Minimums for single files, relative to the workspace root.
+    pub files: Vec<(PathBuf, Minimum)>,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Minimum {
+    pub lines: Option<f64>,
+    pub functions: Option<f64>,
+}
+
+/// A minimum that wasn't reached.
+struct Failure {
+    name: String,
+    kind: &'static str,
+    coverage: f64,
+    minimum: f64,
+}
+
+impl Thresholds {
+    /// Reads the thresholds from the `coverage` table of the travis-cargo configuration, which
+    /// looks like
+    ///
+    /// ```toml
+    /// [package.metadata.travis-cargo.coverage]
+    /// fail-under-lines = 80
+    /// fail-under-functions = 75
+    /// fail-under-patch = 90
+    ///
+    /// [package.metadata.travis-cargo.coverage.files]
+    /// "src/lib.rs" = 90
+    /// "src/parser.rs" = { lines = 95, functions = 100 }
+    /// ```
+    ///
+    /// A file's minimum on its own is for its lines. `--fail-under-lines`,
+    /// `--fail-under-functions` and `--fail-under-patch` take precedence over the configuration.
+    pub fn new(config: Option<&Json>,
+               lines: Option<f64>,
+               functions: Option<f64>,
+               patch: Option<f64>)
+               -> Result<Thresholds> {
+        let config = config.and_then(|config| config.find("coverage"));
+
+        let mut thresholds = Thresholds {
+            lines: match lines {
+                Some(lines) => Some(try!(check_percent(lines, "--fail-under-lines"))),
+                None => try!(optional_percent(config, "coverage", "fail-under-lines")),
+            },
+            functions: match functions {
+                Some(functions) => Some(try!(check_percent(functions, "--fail-under-functions"))),
+                None => try!(optional_percent(config, "coverage", "fail-under-functions")),
+            },
+            patch: match patch {
+                Some(patch) => Some(try!(check_percent(patch, "--fail-under-patch"))),
+                None => try!(optional_percent(config, "coverage", "fail-under-patch")),
+            },
+            files: vec![],
+        };
+
+        if let Some(files) = config.and_then(|config| config.find("files")) {
+            let files = try!(files.as_object()
+                                  .ok_or_else(|| invalid_config("coverage.files", "a table")));
+            for (file, minimum) in files {
+                let key = format!("coverage.files.\"{}\"", file);
+                let minimum = if minimum.is_object() {
+                    Minimum {
+                        lines: try!(optional_percent(Some(minimum), &key, "lines")),
+                        functions: try!(optional_percent(Some(minimum), &key, "functions")),
+                    }
+                } else {
+                    Minimum {
+                        lines: Some(try!(percent(minimum, &key))),
+                        functions: None,
+                    }
+                };
+                thresholds.files.push((PathBuf::from(file), minimum));
+            }
+        }
+
+        Ok(thresholds)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_none() && self.functions.is_none() && self.patch.is_none() &&
+        self.files.is_empty()
+    }
+
+    /// Checks the report against the thresholds, printing a table of the files that fall short.
+    /// The patch minimum only applies if there is a patch.
+    pub fn check(&self,
+                 report: &Report,
+                 patch: Option<&PatchCoverage>,
+                 source_root: &Path)
+                 -> Result<()> {
+        let mut failures = vec![];
+
+        for &(ref file, ref minimum) in &self.files {
+            let name = file.display().to_string();
+            // a file that isn't in the report wasn't run at all.
+            let coverage = report.files.get(&source_root.join(file));
+
+            if let Some(minimum) = minimum.lines {
+                let lines = coverage.map_or(0.0, |coverage| {
+                    percentage(coverage.lines_covered(), coverage.lines.len())
+                });
+                check(&mut failures, &name, "lines", lines, minimum);
+            }
+            if let Some(minimum) = minimum.functions {
+                let functions = coverage.map_or(0.0, |coverage| {
+                    percentage(coverage.functions_covered(), coverage.functions.len())
+                });
+                check(&mut failures, &name, "functions", functions, minimum);
+            }
+        }
+
+        if let Some(minimum) = self.lines {
+            let (covered, total) = report.lines();
+            check(&mut failures, "total", "lines", percentage(covered, total), minimum);
+        }
+        if let Some(minimum) = self.functions {
+            let (covered, total) = report.functions();
+            check(&mut failures, "total", "functions", percentage(covered, total), minimum);
+        }
+        if let (Some(minimum), Some(patch)) = (self.patch, patch) {
+            let (covered, total) = patch.lines();
+            check(&mut failures, "patch", "lines", percentage(covered, total), minimum);
+        }
+
+        if failures.is_empty() {
+            return Ok(());
+        }
+
+        print_table(&failures);
+        Err(Error::CoverageBelowMinimum(failures.len()))
+    }
+}
+
+fn check(failures: &mut Vec<Failure>, name: &str, kind: &'static str, coverage: f64, minimum: f64) {
+    if coverage < minimum {
+        failures.push(Failure {
+            name: name.to_owned(),
+            kind: kind,
+            coverage: coverage,
+            minimum: minimum,
+        });
+    }
+}
+
+fn print_table(failures: &[Failure]) {
+    let width = failures.iter().map(|failure| failure.name.len()).max().unwrap_or(0).max(4);
+
+    println!("Coverage is below the minimum:");
+    println!("    {:<width$}  {:<9}  {:>8}  {:>8}",
+             "File",
+             "Kind",
+             "Coverage",
+             "Minimum",
+             width = width);
+    for failure in failures {
+        println!("    {:<width$}  {:<9}  {:>7.2}%  {:>7.2}%",
+                 failure.name,
+                 failure.kind,
+                 failure.coverage,
+                 failure.minimum,
+                 width = width);
+    }
+}
+
+fn percent(value: &Json, key: &str) -> Result<f64> {
+    match value.as_f64() {
+        Some(value) if 0.0 <= value && value <= 100.0 => Ok(value),
+        _ => Err(invalid_config(key, "a percentage between 0 and 100")),
+    }
+}
+
+fn optional_percent(table: Option<&Json>, table_key: &str, key: &str) -> Result<Option<f64>> {
+    match table.and_then(|table| table.find(key)) {
+        Some(value) => percent(value, &format!("{}.{}", table_key, key)).map(Some),
+        None => Ok(None),
+    }
+}
+
+fn check_percent(value: f64, flag: &str) -> Result<f64> {
+    if 0.0 <= value && value <= 100.0 {
+        Ok(value)
+    } else {
+        Err(Error::InvalidArgument(format!("{} must be a percentage between 0 and 100, not {}",
+                                           flag,
+                                           value)))
+    }
+}
+
+fn invalid_config(key: &str, expected: &str) -> Error {
+    Error::ManifestParse(format!("`travis-cargo.{}` must be {}", key, expected))
+}
@@ -0,0 +1,117 @@
+//! Authentication for pushing docs, which keeps secrets out of URLs and command lines.
+
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::prelude::*;
+use std::path::PathBuf;
+use std::process::{self, Command};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use error::Result;
+
+/// Numbers the temporary files of a process.
+static FILES: AtomicUsize = AtomicUsize::new(0);
+
+/// The environment variable that the askpass helper reads the token from.
+const TOKEN_VAR: &'static str = "TRAVIS_CARGO_GIT_TOKEN";
+
+#[derive(Debug, Clone)]
+pub enum Auth {
+    /// Whatever git is set up to use.
+    None,
+    /// A token for HTTPS remotes, which is given to git by a `GIT_ASKPASS` helper. The user name
+    /// belongs in the remote URL, e.g. `https://x-access-token@github.com/owner/repo.git`.
+    Token(String),
+    /// The private key of an SSH deploy key.
+    SshKey(String),
+}
+
+/// The files that git needs for an `Auth`, which are removed when this is dropped.
+#[derive(Debug)]
+pub struct Credentials {
+    env: Vec<(&'static str, String)>,
+    files: Vec<PathBuf>,
+}
+
+impl Credentials {
+    pub fn new(auth: &Auth) -> Result<Credentials> {
+        let mut credentials = Credentials {
+            env: vec![],
+            files: vec![],
+        };
+
+        match *auth {
+            Auth::None => {}
+            Auth::Token(ref token) => {
+                // The helper is asked for the password, and prints the token from its
+                // environment, so that it isn't part of any command line or file.
+                let askpass = try!(credentials.file("askpass",
+                                                    0o700,
+                                                    &format!("#!/bin/sh\necho \"${}\"\n",
+                                                             TOKEN_VAR)));
+                credentials.env.push(("GIT_ASKPASS", askpass.display().to_string()));
+                credentials.env.push((TOKEN_VAR, token.clone()));
+                credentials.env.push(("GIT_TERMINAL_PROMPT", "0".to_owned()));
+            }
+            Auth::SshKey(ref key) => {
+                // Keys pasted into a CI setting sometimes end up with escaped newlines.
+                let mut key = if key.contains('\n') {
+                    key.clone()
+                } else {
+                    key.replace("\\n", "\n")
+                };
+                if !key.ends_with('\n') {
+                    key.push('\n');
+                }
+
+                let key = try!(credentials.file("deploy-key", 0o600, &key));
+                credentials.env.push(("GIT_SSH_COMMAND",
+                                      format!("ssh -i '{}' -o IdentitiesOnly=yes -o \
+                                               StrictHostKeyChecking=accept-new",
+                                              key.display())));
+            }
+        }
+
+        Ok(credentials)
+    }
+
+    /// Sets up the environment of a git command to authenticate with these credentials.
+    pub fn apply(&self, git: &mut Command) {
+        for &(var, ref value) in &self.env {
+            git.env(var, value);
+        }
+    }
+
+    fn file(&mut self, name: &str, mode: u32, contents: &str) -> Result<PathBuf> {
+        let path = env::temp_dir().join(format!("travis-cargo-{}-{}-{}",
+                                                name,
+                                                process::id(),
+                                                FILES.fetch_add(1, Ordering::SeqCst)));
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        set_mode(&mut options, mode);
+
+        let mut file = try!(options.open(&path));
+        self.files.push(path.clone());
+        try!(file.write_all(contents.as_bytes()));
+        Ok(path)
+    }
+}
+
+impl Drop for Credentials {
+    fn drop(&mut self) {
+        for file in &self.files {
+            let _ = fs::remove_file(file);
+        }
+    }
+}
+
+#[cfg(unix)]
+fn set_mode(options: &mut OpenOptions, mode: u32) {
+    use std::os::unix::fs::OpenOptionsExt;
+
+    options.mode(mode);
+}
+
+#[cfg(not(unix))]
+fn set_mode(_: &mut OpenOptions, _: u32) {}
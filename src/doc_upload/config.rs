@@ -0,0 +1,55 @@
+use rustc_serialize::json::Json;
+
+use error::{Error, Result};
+
+/// The options of `doc-upload`, each of which is `None` when it isn't given.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Options {
+    pub branch: Option<String>,
+    pub version_dir: Option<String>,
+    pub remote: Option<String>,
+    pub target_branch: Option<String>,
+    pub target_dir: Option<String>,
+    pub host: Option<String>,
+}
+
+impl Options {
+    /// Fills in the options that weren't given on the command line from the `doc-upload` table
+    /// of the travis-cargo configuration, which looks like
+    ///
+    /// ```toml
+    /// [package.metadata.travis-cargo.doc-upload]
+    /// branch = "main"
+    /// version-dir = "tag"
+    /// remote = "https://gitlab.com/owner/repo.git"
+    /// target-branch = "pages"
+    /// target-dir = "docs"
+    /// host = "gitlab.com"
+    /// ```
+    pub fn with_config(self, config: Option<&Json>) -> Result<Options> {
+        let config = config.and_then(|config| config.find("doc-upload"));
+
+        Ok(Options {
+            branch: try!(or_config(self.branch, config, "branch")),
+            version_dir: try!(or_config(self.version_dir, config, "version-dir")),
+            remote: try!(or_config(self.remote, config, "remote")),
+            target_branch: try!(or_config(self.target_branch, config, "target-branch")),
+            target_dir: try!(or_config(self.target_dir, config, "target-dir")),
+            host: try!(or_config(self.host, config, "host")),
+        })
+    }
+}
+
+fn or_config(option: Option<String>, config: Option<&Json>, key: &str) -> Result<Option<String>> {
+    if option.is_some() {
+        return Ok(option);
+    }
+
+    match config.and_then(|config| config.find(key)) {
+        Some(&Json::String(ref value)) => Ok(Some(value.clone())),
+        Some(_) => {
+            Err(Error::ManifestParse(format!("`travis-cargo.doc-upload.{}` must be a string", key)))
+        }
+        None => Ok(None),
+    }
+}
@@ -0,0 +1,149 @@
+//! The page at the root of the docs, which links to the crates of the workspace.
+
+use std::cmp;
+use std::fs::File;
+use std::io::prelude::*;
+
+use Manifest;
+
+use super::{doc_title, escape_html};
+
+/// The `index.html` of the docs. A single library is redirected to. Otherwise, each library is
+/// listed with its version and description, and binary-only packages are shown with their
+/// rendered README instead, since `cargo doc` doesn't document them.
+pub fn index_html(manifest: &Manifest) -> String {
+    let members = manifest.workspace_members();
+    let (libs, bins): (Vec<_>, Vec<_>) = members.iter()
+                                                .partition(|package| package.lib_name().is_some());
+    if libs.len() == 1 && bins.is_empty() {
+        return format!("<meta http-equiv=refresh content=0;url={}/index.html>\n",
+                       libs[0].lib_name().unwrap());
+    }
+
+    let title = doc_title(manifest);
+    let mut html = format!("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+                            <title>{0}</title>\n</head>\n<body>\n<h1>{0}</h1>\n",
+                           escape_html(&title));
+
+    if !libs.is_empty() {
+        html.push_str("<ul>\n");
+        for package in &libs {
+            html.push_str(&format!("<li><a href=\"{}/index.html\">{}</a> {}",
+                                   package.lib_name().unwrap(),
+                                   escape_html(&package.name),
+                                   escape_html(&package.version)));
+            if let Some(ref description) = package.description {
+                html.push_str(&format!(" &mdash; {}", escape_html(description)));
+            }
+            html.push_str("</li>\n");
+        }
+        html.push_str("</ul>\n");
+    }
+
+    for package in &bins {
+        html.push_str(&format!("<h2>{} {}</h2>\n",
+                               escape_html(&package.name),
+                               escape_html(&package.version)));
+        if let Some(ref description) = package.description {
+            html.push_str(&format!("<p>{}</p>\n", escape_html(description)));
+        }
+
+        // cargo defaults `readme` to README.md, and reports it even if there's no such file.
+        let readme = package.readme.as_ref().map_or("README.md", |readme| &readme[..]);
+        let readme = package.manifest_dir().join(readme);
+        let mut text = String::new();
+        if File::open(&readme).and_then(|mut file| file.read_to_string(&mut text)).is_ok() {
+            html.push_str(&markdown_html(&text));
+        }
+    }
+
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+/// Renders the blocks of a Markdown README: headings, paragraphs, lists and fenced code blocks,
+/// with inline code. Headings are moved down two levels to fit under the package's `<h2>`. Other
+/// Markdown, such as links and emphasis, is left as text.
+fn markdown_html(markdown: &str) -> String {
+    let mut html = String::new();
+    let mut paragraph: Vec<&str> = vec![];
+    let mut in_list = false;
+    let mut code_block: Option<String> = None;
+
+    for line in markdown.lines() {
+        let fence = line.trim_start().starts_with("```");
+        if let Some(code) = code_block.take() {
+            if fence {
+                html.push_str(&format!("<pre><code>{}</code></pre>\n", escape_html(&code)));
+            } else {
+                code_block = Some(code + line + "\n");
+            }
+            continue;
+        }
+
+        let trimmed = line.trim();
+        let level = trimmed.chars().take_while(|&c| c == '#').count();
+        let is_heading = level >= 1 && level <= 6 &&
+                         (trimmed.len() == level || trimmed[level..].starts_with(' '));
+        let item = if trimmed.starts_with("- ") || trimmed.starts_with("* ") {
+            Some(&trimmed[2..])
+        } else {
+            None
+        };
+
+        if trimmed.is_empty() || fence || is_heading || item.is_some() {
+            end_paragraph(&mut html, &mut paragraph);
+        }
+        if in_list && item.is_none() {
+            html.push_str("</ul>\n");
+            in_list = false;
+        }
+
+        if fence {
+            code_block = Some(String::new());
+        } else if is_heading {
+            let level = cmp::min(level + 2, 6);
+            let text = trimmed.trim_matches('#').trim();
+            html.push_str(&format!("<h{0}>{1}</h{0}>\n", level, inline_html(text)));
+        } else if let Some(item) = item {
+            if !in_list {
+                html.push_str("<ul>\n");
+                in_list = true;
+            }
+            html.push_str(&format!("<li>{}</li>\n", inline_html(item)));
+        } else if !trimmed.is_empty() {
+            paragraph.push(trimmed);
+        }
+    }
+
+    end_paragraph(&mut html, &mut paragraph);
+    if in_list {
+        html.push_str("</ul>\n");
+    }
+    if let Some(code) = code_block {
+        html.push_str(&format!("<pre><code>{}</code></pre>\n", escape_html(&code)));
+    }
+    html
+}
+
+fn end_paragraph(html: &mut String, paragraph: &mut Vec<&str>) {
+    if !paragraph.is_empty() {
+        html.push_str(&format!("<p>{}</p>\n", inline_html(&paragraph.join("\n"))));
+        paragraph.clear();
+    }
+}
+
+/// Escapes `text`, and renders its `code` spans.
+fn inline_html(text: &str) -> String {
+    let escaped = escape_html(text);
+    // An unmatched backtick is just a backtick.
+    if escaped.matches('`').count() % 2 == 1 {
+        return escaped;
+    }
+    escaped.split('`')
+           .enumerate()
+           .map(|(i, part)| {
+               if i % 2 == 1 { format!("<code>{}</code>", part) } else { part.to_owned() }
+           })
+           .collect()
+}
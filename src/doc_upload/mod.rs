@@ -0,0 +1,241 @@
+use std::env;
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::Path;
+
+use {CiEnvironment, Manifest};
+use error::{Error, Result};
+use utils;
+
+use self::auth::Auth;
+use self::config::Options;
+use self::publish::Content;
+
+pub mod auth;
+pub mod config;
+pub mod landing;
+pub mod publish;
+pub mod versions;
+
+const USAGE: &'static str = r"
+usage: travis_cargo doc-upload [-h] [options]
+
+Commit cargo-rendered docs to the gh-pages branch, and push it to GitHub Pages,
+from the master branch.
+
+Pushing authenticates with the private key of a deploy key in `DEPLOY_KEY` if
+it is set, and otherwise with a token in `GH_TOKEN`, which is only optional
+with `--remote`. Neither is put on a command line or in the remote's URL.
+
+Every option but `--help` can also be set in the `doc-upload` table of the
+travis-cargo configuration, e.g. `[package.metadata.travis-cargo.doc-upload]`
+with `target-branch = 'pages'`, and the command line takes precedence.
+
+optional arguments:
+  -h, --help            show this help message and exit
+  --branch BRANCH       upload docs when on this branch, defaults to master
+  --version-dir MODE    upload the docs into a directory of their own, keeping
+                        those of other versions, and list every version in the
+                        top-level index.html and versions.json. MODE is `tag`
+                        (the tag being built, which is uploaded regardless of
+                        `--branch`), `branch`, `latest` or `version` (the
+                        package's version). A `/` in a tag or branch becomes
+                        a `-`
+  --remote URL          push to this remote name or URL, e.g. that of a
+                        separate docs repository, instead of the repository
+                        being built on `--host`
+  --target-branch NAME  the branch to push to, defaults to gh-pages
+  --target-dir PATH     the directory of the branch to upload into, e.g.
+                        `docs`, leaving the rest of the branch alone,
+                        defaults to the whole branch
+  --host HOST           the host of the repository being built, e.g.
+                        gitlab.com, defaults to github.com";
+
+#[derive(Debug, RustcDecodable)]
+struct Args {
+    flag_branch: Option<String>,
+    flag_version_dir: Option<String>,
+    flag_remote: Option<String>,
+    flag_target_branch: Option<String>,
+    flag_target_dir: Option<String>,
+    flag_host: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum VersionDir {
+    Tag,
+    Branch,
+    Latest,
+    Version,
+}
+
+impl VersionDir {
+    fn parse(mode: &str) -> Result<VersionDir> {
+        match &mode.to_lowercase()[..] {
+            "tag" => Ok(VersionDir::Tag),
+            "branch" => Ok(VersionDir::Branch),
+            "latest" => Ok(VersionDir::Latest),
+            "version" => Ok(VersionDir::Version),
+            _ => {
+                Err(Error::InvalidArgument(format!("unknown version directory mode `{}`, \
+                                                    expected `tag`, `branch`, `latest` or \
+                                                    `version`",
+                                                   mode)))
+            }
+        }
+    }
+}
+
+/// Runs `travis-cargo doc-upload`, whose command line is `argv`.
+pub fn doc_upload(manifest: &Manifest, ci: &dyn CiEnvironment, argv: &[String]) -> Result<()> {
+    let args: Args = match try!(utils::parse_args(USAGE, argv)) {
+        Some(args) => args,
+        None => return Ok(()),
+    };
+
+    let options = Options {
+        branch: args.flag_branch,
+        version_dir: args.flag_version_dir,
+        remote: args.flag_remote,
+        target_branch: args.flag_target_branch,
+        target_dir: args.flag_target_dir,
+        host: args.flag_host,
+    };
+    let options = try!(options.with_config(manifest.config()));
+    let mode = match options.version_dir {
+        Some(ref mode) => Some(try!(VersionDir::parse(mode))),
+        None => None,
+    };
+
+    let branch = try!(ci.branch().ok_or(Error::MissingCiInfo("branch")));
+    let pr = ci.pull_request().is_some();
+
+    let version_dir = match mode {
+        Some(VersionDir::Tag) => {
+            Some(versions::dir_name(&try!(ci.tag().ok_or(Error::MissingCiInfo("tag")))))
+        }
+        Some(VersionDir::Branch) => Some(versions::dir_name(&branch)),
+        Some(VersionDir::Latest) => Some("latest".to_owned()),
+        Some(VersionDir::Version) => {
+            let package = try!(manifest.root_package().ok_or_else(|| {
+                Error::InvalidArgument("`--version-dir version` needs a package, not a virtual \
+                                        workspace"
+                                           .to_owned())
+            }));
+            Some(package.version.clone())
+        }
+        None => None,
+    };
+    let upload_branch = if mode == Some(VersionDir::Tag) {
+        true
+    } else {
+        branch == options.branch.unwrap_or("master".to_owned())
+    };
+
+    if upload_branch && !pr && ci.is_secure_env_available() {
+        // only load the secrets when we're sure we're uploading (travis
+        // won't decrypt secret keys for PRs, so loading this with the
+        // other vars causes problems with tests)
+        let host = options.host.unwrap_or("github.com".to_owned());
+        let deploy_key = env::var("DEPLOY_KEY").ok().and_then(|key| {
+            if key.is_empty() { None } else { Some(key) }
+        });
+        let (repo_url, auth) = match (options.remote, deploy_key) {
+            (Some(remote), Some(key)) => (remote, Auth::SshKey(key)),
+            (Some(remote), None) => {
+                // The remote may not need a token, e.g. when it's a path or its URL has one.
+                match utils::env_var("GH_TOKEN") {
+                    Ok(token) => (remote, Auth::Token(token)),
+                    Err(_) => (remote, Auth::None),
+                }
+            }
+            (None, deploy_key) => {
+                let repo = try!(ci.repo_slug().ok_or(Error::MissingCiInfo("repository")));
+                match deploy_key {
+                    Some(key) => (format!("git@{}:{}.git", host, repo), Auth::SshKey(key)),
+                    None => {
+                        (format!("https://x-access-token@{}/{}.git", host, repo),
+                         Auth::Token(try!(utils::env_var("GH_TOKEN"))))
+                    }
+                }
+            }
+        };
+        let target_branch = options.target_branch.unwrap_or("gh-pages".to_owned());
+        let target_dir = options.target_dir.unwrap_or_default();
+        let target_dir = target_dir.trim_matches('/');
+
+        println!("uploading docs...");
+        let doc_dir = manifest.target_directory().join("doc");
+        let mut file = try!(File::create(doc_dir.join("index.html")));
+        try!(file.write_all(landing::index_html(manifest).as_bytes()));
+
+        let target = publish::Target {
+            remote: &repo_url,
+            branch: &target_branch,
+            auth: auth,
+        };
+        let repo = Path::new(".");
+        let parent = try!(publish::fetch(repo, &target));
+
+        let version_path = version_dir.as_ref().map(|version_dir| join(target_dir, version_dir));
+        let index_path = join(target_dir, "index.html");
+        let versions_path = join(target_dir, "versions.json");
+        let nojekyll_path = join(target_dir, ".nojekyll");
+
+        let mut changes = vec![];
+        match (&version_dir, &version_path) {
+            (&Some(ref version_dir), &Some(ref version_path)) => {
+                // The versions are the directories that the last upload listed, since the
+                // branch may have held other things before.
+                let mut versions = vec![];
+                if let Some(ref parent) = parent {
+                    let dirs = try!(publish::dirs(repo, parent, target_dir));
+                    let listed = try!(publish::file(repo, parent, &versions_path));
+                    versions = versions::parse_versions_json(&listed.unwrap_or_default());
+                    versions.retain(|version| dirs.contains(version));
+                }
+                versions.push(version_dir.clone());
+                versions::sort(&mut versions);
+
+                changes.push((&version_path[..], Content::Dir(&doc_dir)));
+                let title = format!("{} documentation", doc_title(manifest));
+                changes.push((&index_path[..],
+                              Content::File(versions::index_html(&title, &versions)
+                                                .into_bytes())));
+                changes.push((&versions_path[..],
+                              Content::File(versions::versions_json(&versions).into_bytes())));
+            }
+            _ => changes.push((target_dir, Content::Dir(&doc_dir))),
+        }
+        // Keep GitHub Pages from running the docs through Jekyll, which drops files starting with
+        // an underscore.
+        changes.push((&nojekyll_path[..], Content::File(vec![])));
+
+        let message = format!("Update documentation for {}",
+                              ci.commit().unwrap_or("HEAD".to_owned()));
+        try!(publish::publish(repo, &target, parent.as_ref().map(|p| &p[..]), &changes, &message));
+    }
+
+    Ok(())
+}
+
+/// The name of the root package, or of the workspace's directory.
+fn doc_title(manifest: &Manifest) -> String {
+    match manifest.root_package() {
+        Some(package) => package.name.clone(),
+        None => {
+            manifest.workspace_root()
+                    .file_name()
+                    .map_or("Documentation".to_owned(), |name| name.to_string_lossy().into_owned())
+        }
+    }
+}
+
+/// `name` under the directory `dir` of a branch, where `""` is its root.
+fn join(dir: &str, name: &str) -> String {
+    if dir.is_empty() { name.to_owned() } else { format!("{}/{}", dir, name) }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
@@ -0,0 +1,303 @@
+//! Publishes files to a branch such as `gh-pages`. The commit is built with git's plumbing
+//! commands, so neither the working tree nor the index of the repository is touched.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use error::{Error, Result};
+use utils;
+
+use super::auth::{Auth, Credentials};
+
+/// Numbers the temporary indexes of a process, which may publish more than once at a time.
+static INDEXES: AtomicUsize = AtomicUsize::new(0);
+
+/// Where to publish to.
+#[derive(Debug, Clone)]
+pub struct Target<'a> {
+    /// A remote name or URL that `git push` accepts.
+    pub remote: &'a str,
+    pub branch: &'a str,
+    pub auth: Auth,
+}
+
+/// What to put at a path of the branch.
+#[derive(Debug, Clone)]
+pub enum Content<'a> {
+    /// The files of a directory, replacing everything that was under the path.
+    Dir(&'a Path),
+    File(Vec<u8>),
+}
+
+/// Fetches the target branch, returning its commit, or `None` if it doesn't exist yet.
+pub fn fetch(repo: &Path, target: &Target) -> Result<Option<String>> {
+    let credentials = try!(Credentials::new(&target.auth));
+    let mut fetch = remote_git(repo, &target.auth, &credentials);
+    // The error message is matched below, so it mustn't be translated.
+    fetch.env("LC_ALL", "C")
+         .args(&["fetch", "--quiet", "--no-tags", target.remote])
+         .arg(format!("refs/heads/{}", target.branch));
+    let fetched = try!(fetch.output());
+    if !fetched.status.success() {
+        let stderr = String::from_utf8_lossy(&fetched.stderr);
+        if stderr.contains("couldn't find remote ref") {
+            return Ok(None);
+        }
+        print!("{}", stderr);
+        return Err(Error::CommandFailed {
+            command: format!("{:?}", fetch),
+            status: fetched.status,
+        });
+    }
+    output(git(repo).args(&["rev-parse", "--verify", "FETCH_HEAD^{commit}"])).map(Some)
+}
+
+/// The names of the directories at `path` of a commit.
+pub fn dirs(repo: &Path, commit: &str, path: &str) -> Result<Vec<String>> {
+    let tree = format!("{}:{}", commit, path);
+    if !try!(exists(repo, &tree)) {
+        return Ok(vec![]);
+    }
+
+    let listing = try!(output(git(repo).args(&["ls-tree",
+                                               "-d",
+                                               "--name-only",
+                                               "--full-tree",
+                                               &tree])));
+    Ok(listing.lines().map(String::from).collect())
+}
+
+/// The contents of the file at `path` of a commit, if there is one.
+pub fn file(repo: &Path, commit: &str, path: &str) -> Result<Option<String>> {
+    let blob = format!("{}:{}", commit, path);
+    if !try!(exists(repo, &blob)) {
+        return Ok(None);
+    }
+    utils::run_output(git(repo).args(&["cat-file", "blob", &blob])).map(Some)
+}
+
+fn exists(repo: &Path, object: &str) -> Result<bool> {
+    let status = git(repo).args(&["cat-file", "-e", object]).stderr(Stdio::null()).status();
+    Ok(try!(status).success())
+}
+
+/// Commits the changes on top of `parent`, which is usually what `fetch` returned, and pushes
+/// them to the target. Changes are applied in order, and paths are relative to the root of the
+/// branch, with `""` being the root itself. The commit is made in the objects of the repository
+/// at `repo`, and its id is returned, or `None` if nothing changed.
+pub fn publish(repo: &Path,
+               target: &Target,
+               parent: Option<&str>,
+               changes: &[(&str, Content)],
+               message: &str)
+               -> Result<Option<String>> {
+    // The path and then the mode and object of each file on the branch.
+    let mut files = BTreeMap::new();
+    if let Some(parent) = parent {
+        let listing = try!(utils::run_output(git(repo).args(&["ls-tree",
+                                                              "-r",
+                                                              "-z",
+                                                              "--full-tree",
+                                                              parent])));
+        for entry in listing.split('\0').filter(|entry| !entry.is_empty()) {
+            // `<mode> SP <type> SP <object> TAB <path>`
+            let mut parts = entry.splitn(2, '\t');
+            let info: Vec<_> = parts.next().unwrap().split(' ').collect();
+            if let (3, Some(path)) = (info.len(), parts.next()) {
+                files.insert(path.to_owned(), (info[0].to_owned(), info[2].to_owned()));
+            }
+        }
+    }
+
+    for &(path, ref content) in changes {
+        let path = path.trim_matches('/');
+        let replaced: Vec<_> = files.keys()
+                                    .filter(|file| is_under(file, path))
+                                    .cloned()
+                                    .collect();
+        for file in replaced {
+            files.remove(&file);
+        }
+
+        match *content {
+            Content::Dir(dir) => {
+                for (name, mode, object) in try!(hash_dir(repo, dir)) {
+                    files.insert(join(path, &name), (mode.to_owned(), object));
+                }
+            }
+            Content::File(ref contents) => {
+                let object = try!(utils::run_input(git(repo).args(&["hash-object",
+                                                                    "-w",
+                                                                    "--stdin"]),
+                                                   contents));
+                files.insert(path.to_owned(), (FILE.to_owned(), object.trim().to_owned()));
+            }
+        }
+    }
+
+    let tree = try!(write_tree(repo, &files));
+    if let Some(parent) = parent {
+        let parent_tree = format!("{}^{{tree}}", parent);
+        let parent_tree = try!(output(git(repo).args(&["rev-parse", &parent_tree])));
+        if parent_tree == tree {
+            println!("{} is already up to date", target.branch);
+            return Ok(None);
+        }
+    }
+
+    let mut commit_tree = git(repo);
+    commit_tree.args(&["commit-tree", &tree, "-m", message]);
+    if let Some(parent) = parent {
+        commit_tree.args(&["-p", parent]);
+    }
+    // CI machines often don't have an identity configured.
+    if !try!(git(repo).args(&["config", "user.email"]).stdout(Stdio::null()).status()).success() {
+        commit_tree.env("GIT_AUTHOR_NAME", "travis-cargo")
+                   .env("GIT_AUTHOR_EMAIL", "travis-cargo@localhost")
+                   .env("GIT_COMMITTER_NAME", "travis-cargo")
+                   .env("GIT_COMMITTER_EMAIL", "travis-cargo@localhost");
+    }
+    let commit = try!(output(&mut commit_tree));
+
+    let credentials = try!(Credentials::new(&target.auth));
+    try!(utils::run(remote_git(repo, &target.auth, &credentials)
+                        .args(&["push", "--quiet", target.remote])
+                        .arg(format!("{}:refs/heads/{}", commit, target.branch))));
+
+    Ok(Some(commit))
+}
+
+fn is_under(file: &str, path: &str) -> bool {
+    path.is_empty() || file == path ||
+    (file.starts_with(path) && file[path.len()..].starts_with('/'))
+}
+
+fn join(path: &str, name: &str) -> String {
+    if path.is_empty() { name.to_owned() } else { format!("{}/{}", path, name) }
+}
+
+/// Writes the files in `dir` to the object database, returning their names, modes and objects.
+/// Symlinks are kept as symlinks, like `git add` does.
+fn hash_dir(repo: &Path, dir: &Path) -> Result<Vec<(String, &'static str, String)>> {
+    let dir = try!(dir.canonicalize());
+    let mut files = vec![];
+    try!(find_files(&dir, Path::new(""), &mut files));
+
+    let mut hashed = vec![];
+    let mut paths = String::new();
+    let mut names = vec![];
+    for &(ref file, mode) in &files {
+        let name = try!(file.to_str().ok_or_else(|| {
+            Error::InvalidArgument(format!("{} is not valid unicode", file.display()))
+        }));
+        let name = name.replace('\\', "/");
+        if mode == SYMLINK {
+            // The blob of a symlink is its target.
+            let target = try!(link_target(&dir.join(file)));
+            let object = try!(utils::run_input(git(repo).args(&["hash-object", "-w", "--stdin"]),
+                                               &target));
+            hashed.push((name, mode, object.trim().to_owned()));
+        } else {
+            paths.push_str(&format!("{}\n", dir.join(file).display()));
+            names.push((name, mode));
+        }
+    }
+    let objects = try!(utils::run_input(git(repo).args(&["hash-object", "-w", "--stdin-paths"]),
+                                        paths.as_bytes()));
+
+    for ((name, mode), object) in names.into_iter().zip(objects.lines()) {
+        hashed.push((name, mode, object.to_owned()));
+    }
+    Ok(hashed)
+}
+
+/// Writes a tree of the files to the object database, through an index of its own.
+fn write_tree(repo: &Path, files: &BTreeMap<String, (String, String)>) -> Result<String> {
+    let mut index_info = String::new();
+    for (path, &(ref mode, ref object)) in files {
+        index_info.push_str(&format!("{} {}\t{}\n", mode, object, path));
+    }
+
+    let index = env::temp_dir().join(format!("travis-cargo-index-{}-{}",
+                                             process::id(),
+                                             INDEXES.fetch_add(1, Ordering::SeqCst)));
+    let _ = fs::remove_file(&index);
+    let tree = utils::run_input(git(repo)
+                                    .env("GIT_INDEX_FILE", &index)
+                                    .args(&["update-index", "--add", "--index-info"]),
+                                index_info.as_bytes())
+                   .and_then(|_| output(git(repo).env("GIT_INDEX_FILE", &index).arg("write-tree")));
+    let _ = fs::remove_file(&index);
+    tree
+}
+
+const FILE: &'static str = "100644";
+const EXECUTABLE: &'static str = "100755";
+const SYMLINK: &'static str = "120000";
+
+fn find_files(root: &Path, dir: &Path, files: &mut Vec<(PathBuf, &'static str)>) -> Result<()> {
+    for entry in try!(fs::read_dir(root.join(dir))) {
+        let entry = try!(entry);
+        let path = dir.join(entry.file_name());
+        let metadata = try!(fs::symlink_metadata(entry.path()));
+        if metadata.file_type().is_symlink() {
+            files.push((path, SYMLINK));
+        } else if metadata.is_dir() {
+            try!(find_files(root, &path, files));
+        } else if is_executable(&metadata) {
+            files.push((path, EXECUTABLE));
+        } else {
+            files.push((path, FILE));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn is_executable(metadata: &fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    metadata.permissions().mode() & 0o111 != 0
+}
+
+#[cfg(not(unix))]
+fn is_executable(_: &fs::Metadata) -> bool {
+    false
+}
+
+#[cfg(unix)]
+fn link_target(path: &Path) -> Result<Vec<u8>> {
+    use std::os::unix::ffi::OsStrExt;
+
+    Ok(try!(fs::read_link(path)).as_os_str().as_bytes().to_owned())
+}
+
+#[cfg(not(unix))]
+fn link_target(path: &Path) -> Result<Vec<u8>> {
+    Ok(try!(fs::read_link(path)).to_string_lossy().replace('\\', "/").into_bytes())
+}
+
+fn git(repo: &Path) -> Command {
+    let mut git = Command::new("git");
+    git.arg("-C").arg(repo);
+    git
+}
+
+/// A git command that talks to the remote.
+fn remote_git(repo: &Path, auth: &Auth, credentials: &Credentials) -> Command {
+    let mut git = git(repo);
+    credentials.apply(&mut git);
+    // A credential helper of the machine would be asked before the askpass helper.
+    if let Auth::Token(_) = *auth {
+        git.args(&["-c", "credential.helper="]);
+    }
+    git
+}
+
+fn output(command: &mut Command) -> Result<String> {
+    utils::run_output(command).map(|output| output.trim().to_owned())
+}
@@ -0,0 +1,115 @@
+//! The top-level pages of versioned documentation, which list every deployed version.
+
+use std::cmp::Ordering;
+use std::iter::Peekable;
+
+use rustc_serialize::json::Json;
+
+use super::escape_html;
+
+/// Sorts versions for listing: `latest` first, and then the newest first, comparing runs of
+/// digits as numbers so that `1.10.0` comes before `1.9.0`, and a release before its pre-releases
+/// like semver does.
+pub fn sort(versions: &mut Vec<String>) {
+    versions.sort_by(|a, b| {
+        match (a == "latest", b == "latest") {
+            (true, false) => Ordering::Less,
+            (false, true) => Ordering::Greater,
+            _ => version_cmp(b, a),
+        }
+    });
+    versions.dedup();
+}
+
+/// The directory for the docs of a branch or tag, such as `release-1.0` for `release/1.0`. It's
+/// never nested, so that it's listed as one version.
+pub fn dir_name(branch_or_tag: &str) -> String {
+    branch_or_tag.replace('/', "-")
+}
+
+/// A page linking to the docs of each version.
+pub fn index_html(title: &str, versions: &[String]) -> String {
+    let mut html = format!("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+                            <title>{0}</title>\n</head>\n<body>\n<h1>{0}</h1>\n<ul>\n",
+                           escape_html(title));
+    for version in versions {
+        html.push_str(&format!("<li><a href=\"{0}/index.html\">{0}</a></li>\n",
+                               escape_html(version)));
+    }
+    html.push_str("</ul>\n</body>\n</html>\n");
+    html
+}
+
+/// A list of the versions for a version switcher, such as `["latest","1.1.0","1.0.0"]`. Each one
+/// is the name of a directory next to the file.
+pub fn versions_json(versions: &[String]) -> String {
+    Json::Array(versions.iter().cloned().map(Json::String).collect()).to_string()
+}
+
+/// The versions in a file written by `versions_json`.
+pub fn parse_versions_json(json: &str) -> Vec<String> {
+    Json::from_str(json)
+        .ok()
+        .as_ref()
+        .and_then(Json::as_array)
+        .map(|versions| versions.iter().filter_map(Json::as_string).map(String::from).collect())
+        .unwrap_or_default()
+}
+
+fn version_cmp(a: &str, b: &str) -> Ordering {
+    let (a_core, a_pre) = split_prerelease(a);
+    let (b_core, b_pre) = split_prerelease(b);
+    natural_cmp(a_core, b_core).then_with(|| {
+        match (a_pre, b_pre) {
+            (None, None) => Ordering::Equal,
+            (None, Some(_)) => Ordering::Greater,
+            (Some(_), None) => Ordering::Less,
+            (Some(a_pre), Some(b_pre)) => natural_cmp(a_pre, b_pre),
+        }
+    })
+}
+
+/// Splits `1.0.0-beta.1` into `1.0.0` and `beta.1`. Only a `-` right after a digit starts a
+/// pre-release, so that names like `feature-x` are compared whole.
+fn split_prerelease(version: &str) -> (&str, Option<&str>) {
+    match version.find('-') {
+        Some(i) if version[..i].ends_with(|c: char| c.is_digit(10)) => {
+            (&version[..i], Some(&version[i + 1..]))
+        }
+        _ => (version, None),
+    }
+}
+
+fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a.chars().peekable(), b.chars().peekable());
+    loop {
+        match (a.peek().cloned(), b.peek().cloned()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_digit(10) && y.is_digit(10) => {
+                let x = number(&mut a);
+                let y = number(&mut b);
+                if x != y {
+                    return x.cmp(&y);
+                }
+            }
+            (Some(x), Some(y)) => {
+                if x != y {
+                    return x.cmp(&y);
+                }
+                a.next();
+                b.next();
+            }
+        }
+    }
+}
+
+fn number<I: Iterator<Item = char>>(chars: &mut Peekable<I>) -> u64 {
+    let mut number = 0u64;
+    while let Some(digit) = chars.peek().and_then(|c| c.to_digit(10)) {
+        number = number.saturating_mul(10).saturating_add(digit as u64);
+        chars.next();
+    }
+    number
+}
@@ -0,0 +1,195 @@
+use std::error;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use std::process::ExitStatus;
+use std::result;
+
+pub type Result<T> = result::Result<T, Error>;
+
+#[derive(Debug)]
+pub enum Error {
+    /// Spawning a command or touching the filesystem failed.
+    Io(io::Error),
+
+    /// A required environment variable isn't set.
+    MissingEnvVar(String),
+
+    /// The CI environment doesn't provide a required fact about the build, such as the branch.
+    MissingCiInfo(&'static str),
+
+    /// A command ran, but exited unsuccessfully or was killed by a signal.
+    CommandFailed {
+        command: String,
+        status: ExitStatus,
+    },
+
+    /// The output of `cargo metadata` couldn't be understood.
+    ManifestParse(String),
+
+    /// The output of `rustc -vV` couldn't be understood.
+    ToolchainParse(String),
+
+    /// kcov wasn't found where it was expected to be after building it.
+    KcovNotFound(PathBuf),
+
+    /// The downloaded or given kcov source archive isn't the pinned release.
+    ChecksumMismatch(PathBuf),
+
+    /// Building the tests didn't produce any test binaries to record the coverage of.
+    NoTestBinaries,
+
+    /// No merged coverage report was found in the directory.
+    NoCoverageReport(PathBuf),
+
+    /// The merged coverage report doesn't have any instrumented lines, which usually means that
+    /// the tests were built without debuginfo.
+    EmptyCoverageReport(PathBuf),
+
+    /// A coverage report couldn't be understood.
+    ReportParse(String),
+
+    /// A coverage service didn't accept the upload, with its response.
+    UploadFailed(String),
+
+    /// The coverage is below the minimum in this many places.
+    CoverageBelowMinimum(usize),
+
+    /// An option was given an invalid value.
+    InvalidArgument(String),
+
+    /// An LLVM tool needed by the llvm coverage backend isn't installed.
+    LlvmToolNotFound(&'static str),
+}
+
+impl Error {
+    /// The exit code that `travis-cargo` should exit with for this error.
+    ///
+    /// A failing command passes its own exit code through, so that `travis-cargo test` fails the
+    /// same way as `cargo test`. A command killed by a signal exits with 128 plus the signal
+    /// number, like a shell does.
+    pub fn exit_code(&self) -> i32 {
+        match *self {
+            Error::Io(_) => 1,
+            Error::MissingEnvVar(_) | Error::MissingCiInfo(_) => 2,
+            Error::ManifestParse(_) | Error::ToolchainParse(_) => 3,
+            Error::KcovNotFound(_) | Error::ChecksumMismatch(_) | Error::LlvmToolNotFound(_) => 4,
+            Error::InvalidArgument(_) => 1,
+            Error::NoTestBinaries |
+            Error::NoCoverageReport(_) |
+            Error::EmptyCoverageReport(_) |
+            Error::ReportParse(_) |
+            Error::UploadFailed(_) => 5,
+            Error::CoverageBelowMinimum(_) => 6,
+            Error::CommandFailed { ref status, .. } => {
+                match (status.code(), signal(status)) {
+                    (Some(code), _) => code,
+                    (None, Some(signal)) => 128 + signal,
+                    (None, None) => 1,
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Io(ref err) => write!(f, "{}", err),
+            Error::MissingEnvVar(ref name) => {
+                write!(f, "the environment variable `{}` is not set", name)
+            }
+            Error::MissingCiInfo(fact) => {
+                write!(f, "could not determine the {} from the CI environment", fact)
+            }
+            Error::CommandFailed { ref command, ref status } => {
+                match (status.code(), signal(status)) {
+                    (Some(code), _) => write!(f, "{} failed with exit code {}", command, code),
+                    (None, Some(signal)) => {
+                        write!(f, "{} was killed by signal {}", command, signal)
+                    }
+                    (None, None) => write!(f, "{} failed", command),
+                }
+            }
+            Error::ManifestParse(ref message) => {
+                write!(f, "could not parse the cargo manifest: {}", message)
+            }
+            Error::ToolchainParse(ref message) => {
+                write!(f, "could not parse the output of `rustc -vV`: {}", message)
+            }
+            Error::KcovNotFound(ref path) => {
+                write!(f, "kcov was not found at {}", path.display())
+            }
+            Error::ChecksumMismatch(ref path) => {
+                write!(f, "the checksum of {} does not match kcov's release", path.display())
+            }
+            Error::LlvmToolNotFound(tool) => {
+                write!(f,
+                       "{} was not found, try `rustup component add llvm-tools-preview`",
+                       tool)
+            }
+            Error::NoTestBinaries => write!(f, "no test binaries were built"),
+            Error::NoCoverageReport(ref dir) => {
+                write!(f, "no merged coverage report was found in {}", dir.display())
+            }
+            Error::EmptyCoverageReport(ref dir) => {
+                write!(f,
+                       "the merged coverage report in {} has no instrumented lines, were the tests \
+                        built without debuginfo?",
+                       dir.display())
+            }
+            Error::ReportParse(ref message) => {
+                write!(f, "could not parse the coverage report: {}", message)
+            }
+            Error::UploadFailed(ref response) => {
+                write!(f, "the coverage upload failed: {}", response)
+            }
+            Error::CoverageBelowMinimum(1) => write!(f, "coverage is below 1 minimum"),
+            Error::CoverageBelowMinimum(count) => {
+                write!(f, "coverage is below {} minimums", count)
+            }
+            Error::InvalidArgument(ref message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn description(&self) -> &str {
+        match *self {
+            Error::Io(_) => "I/O error",
+            Error::MissingEnvVar(_) => "missing environment variable",
+            Error::MissingCiInfo(_) => "missing CI information",
+            Error::CommandFailed { .. } => "command failed",
+            Error::ManifestParse(_) => "could not parse the cargo manifest",
+            Error::ToolchainParse(_) => "could not parse the rustc version",
+            Error::KcovNotFound(_) => "kcov not found",
+            Error::ChecksumMismatch(_) => "checksum mismatch",
+            Error::LlvmToolNotFound(_) => "LLVM tool not found",
+            Error::NoTestBinaries => "no test binaries",
+            Error::NoCoverageReport(_) => "no coverage report",
+            Error::EmptyCoverageReport(_) => "empty coverage report",
+            Error::ReportParse(_) => "could not parse the coverage report",
+            Error::UploadFailed(_) => "coverage upload failed",
+            Error::CoverageBelowMinimum(_) => "coverage below minimum",
+            Error::InvalidArgument(_) => "invalid argument",
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::Io(err)
+    }
+}
+
+#[cfg(unix)]
+fn signal(status: &ExitStatus) -> Option<i32> {
+    use std::os::unix::process::ExitStatusExt;
+
+    status.signal()
+}
+
+#[cfg(not(unix))]
+fn signal(_: &ExitStatus) -> Option<i32> {
+    None
+}
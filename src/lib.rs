@@ -1,11 +1,16 @@
 extern crate docopt;
-extern crate regex;
 extern crate rustc_serialize;
 
 pub mod cargo;
+pub mod ci;
 pub mod coverage;
 pub mod doc_upload;
+pub mod error;
 pub mod manifest;
+pub mod toolchain;
 pub mod utils;
 
-pub use manifest::{Manifest, Target};
+pub use ci::CiEnvironment;
+pub use error::{Error, Result};
+pub use manifest::{Manifest, Package, Target};
+pub use toolchain::{Channel, Toolchain, Version, VersionPredicate};
@@ -3,23 +3,35 @@ extern crate rustc_serialize;
 extern crate travis_cargo;
 
 use std::env;
-use std::process::Command;
+use std::process;
 
 use docopt::Docopt;
 
-use travis_cargo::{Manifest, cargo, doc_upload, coverage, utils};
+use travis_cargo::{Manifest, Result, Toolchain, VersionPredicate, cargo, ci, doc_upload,
+                   coverage};
 
 const USAGE: &'static str = r"
 Manages interactions between Travis and Cargo and common tooling tasks.
 
 Usage:
-    travis-cargo [-h] [-q] [--only VERSION] [--skip VERSION] <command> [<args>...]
+    travis-cargo [-h] [-q] [--only VERSION]... [--skip VERSION]... <command> [<args>...]
 
 Options:
     --help -h       show this screen
     --quiet -q      don't pass --verbose to cargo subcommands
-    --only VERSION  only run the given command if the specified version matches `TRAVIS RUST VERSION`
-    --skip VERSION  only run the given command if the specified version does not match `TRAVIS RUST VERSION`
+    --only VERSION  only run the given command if the toolchain matches VERSION. Can be given
+                    several times, in which case all of them have to match
+    --skip VERSION  don't run the given command if the toolchain matches VERSION. Can be given
+                    several times, in which case any of them can match
+
+Versions:
+  VERSION is matched against the toolchain detected from `rustc -vV`. It can be a channel
+  (`stable`, `beta`, `nightly` or `dev`), an exact version (`1.30.0`, or `1.30` for any `1.30.x`),
+  or a comparison with a version (`>=1.30`, `<1.31.0`, ...). The date of the compiler's commit
+  can be bounded too, on its own (`>=2019-06-01`) or after one of the above (`nightly<2020-01-01`).
+  Prefix with `!` to negate, and separate alternatives with commas (`stable,beta`). VERSION also
+  matches the version requested from the CI service verbatim, such as `TRAVIS_RUST_VERSION`,
+  which can also be a dated toolchain (`nightly-2020-01-01`). Any other VERSION is an error.
 
 Subcommands:
   travis-cargo supports all cargo subcommands, and selected others (listed below). Cargo
@@ -29,7 +41,24 @@ Subcommands:
 
     coverage        record code coverage
     coveralls       record and upload code coverage to coveralls.io
+    codecov         record and upload code coverage to codecov.io
     doc-upload      upload documentation to GitHub pages
+
+Exit status:
+    0               success, or the command was skipped by `--only` or `--skip`
+    1               an I/O error, such as a command that could not be started, or an invalid
+                    command line
+    2               a required environment variable is not set, or the CI environment does not
+                    provide a required fact about the build
+    3               the cargo manifest or the output of `rustc -vV` could not be read
+    4               kcov, or an LLVM tool used for coverage, could not be found, or the kcov
+                    source archive did not match its checksum
+    5               no test binaries were built, or the merged coverage report could not be
+                    found, read or uploaded, or it has no instrumented lines
+    6               the coverage is below a minimum given by `--fail-under-lines`,
+                    `--fail-under-functions` or the configuration
+    other           a command run by travis-cargo failed with this exit status (or was killed by
+                    a signal, giving 128 plus the signal number)
 ";
 
 #[derive(Debug, RustcDecodable)]
@@ -37,8 +66,8 @@ struct Args {
     arg_command: String,
     arg_args: Vec<String>,
     flag_quiet: bool,
-    flag_only: Option<String>,
-    flag_skip: Option<String>,
+    flag_only: Vec<String>,
+    flag_skip: Vec<String>,
 }
 
 fn main() {
@@ -46,47 +75,94 @@ fn main() {
                          .and_then(|dopt| dopt.options_first(true).decode())
                          .unwrap_or_else(|e| e.exit());
 
-    let version = env::var("TRAVIS_RUST_VERSION")
-                      .ok()
-                      .unwrap_or_else(|| {
-                          // fill in the version based on the compiler's version output.
-                          let output = utils::run_output(Command::new("rustc").arg("-V"));
-
-                          let phrases = ["nightly", "dev", "beta"];
-                          phrases.iter()
-                                 .map(|&phrase| {
-                                     match phrase {
-                                         "dev" => "nightly",
-                                         _ => phrase,
-                                     }
-                                 })
-                                 .find(|&phrase| output.contains(phrase))
-                                 .unwrap_or_default()
-                                 .to_owned()
-                      });
-
-    if args.flag_only.map(|only| only != version).unwrap_or_default() {
-        return;
+    if let Err(err) = run(args) {
+        eprintln!("travis-cargo: {}", err);
+        process::exit(err.exit_code());
+    }
+}
+
+fn run(args: Args) -> Result<()> {
+    let ci = ci::detect();
+    // Detecting the toolchain runs `rustc`, so it's only done for the commands that need it.
+    let mut detected = None;
+
+    let mut only = vec![];
+    for predicate in &args.flag_only {
+        only.push(try!(VersionPredicate::parse(predicate)));
+    }
+    let mut skip = vec![];
+    for predicate in &args.flag_skip {
+        skip.push(try!(VersionPredicate::parse(predicate)));
     }
 
-    if args.flag_skip.map(|skip| skip == version).unwrap_or_default() {
-        return;
+    if !only.is_empty() || !skip.is_empty() {
+        let toolchain = try!(toolchain(&mut detected));
+        let ci_version = ci.rust_version();
+        let matches = |predicate: &VersionPredicate| {
+            predicate.matches(toolchain, ci_version.as_ref().map(|v| &v[..]))
+        };
+
+        if !only.iter().all(&matches) || skip.iter().any(&matches) {
+            return Ok(());
+        }
     }
 
-    let manifest = Manifest::new(env::current_dir().unwrap());
+    let argv = command_argv(&args);
     match &args.arg_command[..] {
-        "doc-upload" => doc_upload::doc_upload(manifest),
-        "coverage" => coverage::coverage(&version),
-        "coveralls" => coverage::coveralls(&version),
+        "doc-upload" => doc_upload::doc_upload(&try!(manifest()), &*ci, &argv),
+        "coverage" => coverage::coverage(&try!(manifest()), try!(toolchain(&mut detected)), &argv),
+        "coveralls" => {
+            coverage::coveralls(&try!(manifest()), &*ci, try!(toolchain(&mut detected)), &argv)
+        }
+        "codecov" => {
+            coverage::codecov(&try!(manifest()), &*ci, try!(toolchain(&mut detected)), &argv)
+        }
         ref command @ _ => {
+            let toolchain = try!(toolchain(&mut detected));
             if ["build", "bench", "test", "doc", "run", "rustc", "rustdoc"].contains(command) {
-                cargo::cargo_feature(&version, args.flag_quiet, &args.arg_command, &args.arg_args);
+                cargo::cargo_feature(toolchain, args.flag_quiet, &args.arg_command, &args.arg_args)
             } else {
-                cargo::cargo_no_feature(&version,
+                cargo::cargo_no_feature(toolchain,
                                         args.flag_quiet,
                                         &args.arg_command,
-                                        &args.arg_args);
+                                        &args.arg_args)
             }
         }
     }
 }
+
+/// The toolchain, which is detected the first time that it's needed.
+fn toolchain(detected: &mut Option<Toolchain>) -> Result<&Toolchain> {
+    if detected.is_none() {
+        *detected = Some(try!(Toolchain::detect()));
+    }
+    Ok(detected.as_ref().unwrap())
+}
+
+/// The command line of the subcommand, without the options of `travis-cargo` itself. docopt drops
+/// the first `--` of the command line from `<args>`, so the arguments are taken as they were given.
+fn command_argv(args: &Args) -> Vec<String> {
+    let argv: Vec<String> = env::args().collect();
+    let without_separator = |args: &[String]| {
+        let mut args = args.to_vec();
+        if let Some(i) = args.iter().position(|arg| arg == "--") {
+            args.remove(i);
+        }
+        args
+    };
+    let rest = (1..argv.len())
+                   .filter(|&i| argv[i] == args.arg_command)
+                   .map(|i| &argv[i + 1..])
+                   .find(|rest| {
+                       *rest == &args.arg_args[..] || without_separator(rest) == args.arg_args
+                   })
+                   .unwrap_or(&args.arg_args);
+
+    let mut command_argv = vec![argv[0].clone(), args.arg_command.clone()];
+    command_argv.extend(rest.iter().cloned());
+    command_argv
+}
+
+fn manifest() -> Result<Manifest> {
+    Manifest::new(try!(env::current_dir()))
+}
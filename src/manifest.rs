@@ -1,85 +1,358 @@
-use std::path::Path;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
 use std::process::Command;
-use std::str;
 
 use rustc_serialize::json::Json;
 
+use error::{Error, Result};
+use utils;
+
+// The target kinds that cargo considers to be a library.
+const LIB_KINDS: &'static [&'static str] = &["lib", "rlib", "dylib", "cdylib", "staticlib",
+                                             "proc-macro"];
+
+fn string<'a>(json: &'a Json, key: &str) -> Result<&'a str> {
+    json.find(key)
+        .and_then(Json::as_string)
+        .ok_or_else(|| Error::ManifestParse(format!("missing string `{}`", key)))
+}
+
+fn array<'a>(json: &'a Json, key: &str) -> &'a [Json] {
+    json.find(key).and_then(Json::as_array).map(|array| &array[..]).unwrap_or(&[])
+}
+
+fn optional(json: &Json, key: &str) -> Option<String> {
+    json.find(key).and_then(Json::as_string).map(String::from)
+}
+
+fn strings(json: &Json, key: &str) -> Vec<String> {
+    array(json, key).iter().filter_map(Json::as_string).map(String::from).collect()
+}
+
 #[derive(Debug, Clone)]
-pub struct Target(Json);
+pub struct Target {
+    pub name: String,
+    pub kind: Vec<String>,
+    pub crate_types: Vec<String>,
+    pub src_path: PathBuf,
+    pub edition: String,
+    pub required_features: Vec<String>,
+    pub test: bool,
+    pub doctest: bool,
+    pub bench: bool,
+}
 
 impl Target {
-    pub fn binary_name(&self) -> Option<String> {
-        self.0
-            .find("name")
-            .and_then(Json::as_string)
-            .map(|name| name.replace("-", "_"))
-            .and_then(|name| {
-                self.0
-                    .find_path(&["metadata", "extra_filename"])
-                    .and_then(Json::as_string)
-                    .map(|file_name| name + file_name)
-            })
+    fn from_json(json: &Json) -> Result<Target> {
+        let flag = |key, default| json.find(key).and_then(Json::as_boolean).unwrap_or(default);
+
+        let name = try!(string(json, "name")).to_owned();
+        let kind = strings(json, "kind");
+        let src_path = try!(string(json, "src_path"));
+
+        // Older versions of cargo don't report everything, so fall back to cargo's defaults.
+        let crate_types = match strings(json, "crate_types") {
+            ref crate_types if crate_types.is_empty() => kind.clone(),
+            crate_types => crate_types,
+        };
+        let is_lib = is_lib_kind(&kind);
+
+        Ok(Target {
+            name: name,
+            kind: kind,
+            crate_types: crate_types,
+            src_path: PathBuf::from(src_path),
+            edition: json.find("edition").and_then(Json::as_string).unwrap_or("2015").to_owned(),
+            required_features: strings(json, "required-features"),
+            test: flag("test", true),
+            doctest: flag("doctest", is_lib),
+            bench: flag("bench", true),
+        })
+    }
+
+    /// The name of the target as rustc sees it, with dashes replaced by underscores.
+    pub fn crate_name(&self) -> String {
+        self.name.replace("-", "_")
+    }
+
+    pub fn is_lib(&self) -> bool {
+        is_lib_kind(&self.kind)
+    }
+
+    pub fn is_proc_macro(&self) -> bool {
+        self.has_kind("proc-macro")
+    }
+
+    pub fn is_bin(&self) -> bool {
+        self.has_kind("bin")
+    }
+
+    pub fn is_example(&self) -> bool {
+        self.has_kind("example")
+    }
+
+    pub fn is_test(&self) -> bool {
+        self.has_kind("test")
+    }
+
+    pub fn is_bench(&self) -> bool {
+        self.has_kind("bench")
+    }
+
+    pub fn is_custom_build(&self) -> bool {
+        self.has_kind("custom-build")
+    }
+
+    fn has_kind(&self, kind: &str) -> bool {
+        self.kind.iter().any(|k| k == kind)
+    }
+}
+
+fn is_lib_kind(kind: &[String]) -> bool {
+    kind.iter().any(|kind| LIB_KINDS.contains(&&kind[..]))
+}
+
+#[derive(Debug, Clone)]
+pub struct Package {
+    pub id: String,
+    pub name: String,
+    pub version: String,
+    pub authors: Vec<String>,
+    pub description: Option<String>,
+    pub homepage: Option<String>,
+    pub repository: Option<String>,
+    pub license: Option<String>,
+    pub license_file: Option<String>,
+    pub readme: Option<String>,
+    pub rust_version: Option<String>,
+    pub manifest_path: PathBuf,
+    pub targets: Vec<Target>,
+    pub features: BTreeMap<String, Vec<String>>,
+    /// The `[package.metadata]` table, or `Json::Null` if there is none.
+    pub metadata: Json,
+}
+
+impl Package {
+    fn from_json(json: &Json) -> Result<Package> {
+        let mut targets = vec![];
+        for target in array(json, "targets") {
+            targets.push(try!(Target::from_json(target)));
+        }
+
+        let mut features = BTreeMap::new();
+        if let Some(object) = json.find("features").and_then(Json::as_object) {
+            for (feature, enables) in object {
+                let enables = enables.as_array()
+                                     .map(|enables| {
+                                         enables.iter()
+                                                .filter_map(Json::as_string)
+                                                .map(String::from)
+                                                .collect()
+                                     })
+                                     .unwrap_or_default();
+                features.insert(feature.clone(), enables);
+            }
+        }
+
+        Ok(Package {
+            id: try!(string(json, "id")).to_owned(),
+            name: try!(string(json, "name")).to_owned(),
+            version: try!(string(json, "version")).to_owned(),
+            authors: strings(json, "authors"),
+            description: optional(json, "description"),
+            homepage: optional(json, "homepage"),
+            repository: optional(json, "repository"),
+            license: optional(json, "license"),
+            license_file: optional(json, "license_file"),
+            readme: optional(json, "readme"),
+            rust_version: optional(json, "rust_version"),
+            manifest_path: PathBuf::from(try!(string(json, "manifest_path"))),
+            targets: targets,
+            features: features,
+            metadata: json.find("metadata").cloned().unwrap_or(Json::Null),
+        })
+    }
+
+    pub fn lib_name(&self) -> Option<String> {
+        self.targets.iter().find(|target| target.is_lib()).map(Target::crate_name)
+    }
+
+    /// The directory containing the package's `Cargo.toml`.
+    pub fn manifest_dir(&self) -> &Path {
+        self.manifest_path.parent().unwrap()
+    }
+
+    /// The environment variables that cargo sets when it runs the package's tests, see
+    /// <https://doc.rust-lang.org/cargo/reference/environment-variables.html>.
+    pub fn test_env(&self) -> Vec<(&'static str, String)> {
+        // `major.minor.patch-pre+build`
+        let release = self.version.split('+').next().unwrap();
+        let mut release_pre = release.splitn(2, '-');
+        let mut numbers = release_pre.next().unwrap().split('.');
+
+        let optional = |value: &Option<String>| value.clone().unwrap_or_default();
+        vec![("CARGO_MANIFEST_DIR", self.manifest_dir().display().to_string()),
+             ("CARGO_MANIFEST_PATH", self.manifest_path.display().to_string()),
+             ("CARGO_PKG_NAME", self.name.clone()),
+             ("CARGO_PKG_VERSION", self.version.clone()),
+             ("CARGO_PKG_VERSION_MAJOR", numbers.next().unwrap_or("").to_owned()),
+             ("CARGO_PKG_VERSION_MINOR", numbers.next().unwrap_or("").to_owned()),
+             ("CARGO_PKG_VERSION_PATCH", numbers.next().unwrap_or("").to_owned()),
+             ("CARGO_PKG_VERSION_PRE", release_pre.next().unwrap_or("").to_owned()),
+             ("CARGO_PKG_AUTHORS", self.authors.join(":")),
+             ("CARGO_PKG_DESCRIPTION", optional(&self.description)),
+             ("CARGO_PKG_HOMEPAGE", optional(&self.homepage)),
+             ("CARGO_PKG_REPOSITORY", optional(&self.repository)),
+             ("CARGO_PKG_LICENSE", optional(&self.license)),
+             ("CARGO_PKG_LICENSE_FILE", optional(&self.license_file)),
+             ("CARGO_PKG_README", optional(&self.readme)),
+             ("CARGO_PKG_RUST_VERSION", optional(&self.rust_version))]
     }
 }
 
 #[derive(Debug)]
-pub struct Manifest(Json);
+pub struct Manifest {
+    manifest_path: PathBuf,
+    workspace_root: PathBuf,
+    target_directory: PathBuf,
+    workspace_members: Vec<Package>,
+    workspace_metadata: Json,
+}
 
 impl Manifest {
-    pub fn new<P>(dir: P) -> Self
+    pub fn new<P>(dir: P) -> Result<Self>
         where P: AsRef<Path>
     {
-        // the --manifest-path behaviour changed in https://github.com/rust-lang/cargo/pull/1955,
-        // so we need to be careful to handle both
-        let path_file = dir.as_ref().join("Cargo.toml");
-        let path_dir = dir;
-
-        let stdout = {
-            let output = Command::new("cargo")
-                             .args(&["read-manifest",
-                                     "--manifest-path",
-                                     path_file.to_str().unwrap()])
-                             .output()
-                             .unwrap();
-
-            if output.status.success() {
-                output.stdout
-            } else {
-                Command::new("cargo")
-                    .args(&["read-manifest",
-                            "--manifest-path",
-                            path_dir.as_ref().to_str().unwrap()])
-                    .output()
-                    .unwrap()
-                    .stdout
+        let manifest_path = dir.as_ref().join("Cargo.toml");
+
+        // `--no-deps` keeps `packages` down to the workspace members, and avoids resolving (and
+        // possibly fetching) the dependency graph.
+        let output = try!(utils::run_output(Command::new("cargo")
+                                                .args(&["metadata",
+                                                        "--format-version",
+                                                        "1",
+                                                        "--no-deps",
+                                                        "--manifest-path"])
+                                                .arg(&manifest_path)));
+
+        let metadata = try!(Json::from_str(&output)
+                                .map_err(|err| Error::ManifestParse(err.to_string())));
+
+        let member_ids = strings(&metadata, "workspace_members");
+        let mut workspace_members = vec![];
+        for package in array(&metadata, "packages") {
+            let package = try!(Package::from_json(package));
+            if member_ids.contains(&package.id) {
+                workspace_members.push(package);
             }
-        };
+        }
 
-        Manifest(Json::from_str(&str::from_utf8(&stdout).unwrap()).unwrap())
+        Ok(Manifest {
+            manifest_path: manifest_path,
+            workspace_root: PathBuf::from(try!(string(&metadata, "workspace_root"))),
+            target_directory: PathBuf::from(try!(string(&metadata, "target_directory"))),
+            workspace_members: workspace_members,
+            workspace_metadata: metadata.find("metadata").cloned().unwrap_or(Json::Null),
+        })
     }
 
-    pub fn targets(&self) -> Option<Vec<Target>> {
-        let target_json = self.0
-                              .find("targets")
-                              .and_then(Json::as_array)
-                              .unwrap();
+    pub fn workspace_root(&self) -> &Path {
+        &self.workspace_root
+    }
 
-        Some(target_json.iter()
-                        .map(|target_json| Target(target_json.to_owned()))
-                        .collect())
+    pub fn target_directory(&self) -> &Path {
+        &self.target_directory
     }
 
-    pub fn lib_name(&self) -> Option<String> {
-        for target in self.targets().unwrap() {
-            if target.0
-                     .find("kind")
-                     .unwrap()
-                     .as_array()
-                     .unwrap()
-                     .contains(&Json::String("lib".into())) {
-                return Some(target.0.find("name").unwrap().as_string().unwrap().replace("-", "_"));
+    pub fn workspace_members(&self) -> &[Package] {
+        &self.workspace_members
+    }
+
+    /// The package whose manifest was loaded, or `None` for a virtual workspace.
+    pub fn root_package(&self) -> Option<&Package> {
+        let manifest_path = self.manifest_path.canonicalize().ok();
+
+        self.workspace_members
+            .iter()
+            .find(|package| package.manifest_path.canonicalize().ok() == manifest_path)
+    }
+
+    /// The targets of the root package, or of every workspace member for a virtual workspace.
+    pub fn targets(&self) -> Vec<Target> {
+        match self.root_package() {
+            Some(package) => package.targets.clone(),
+            None => {
+                self.workspace_members
+                    .iter()
+                    .flat_map(|package| package.targets.iter().cloned())
+                    .collect()
             }
         }
-        None
     }
+
+    /// The library name of the root package, or of the first workspace member with a library for
+    /// a virtual workspace.
+    pub fn lib_name(&self) -> Option<String> {
+        match self.root_package() {
+            Some(package) => package.lib_name(),
+            None => self.workspace_members.iter().filter_map(Package::lib_name).next(),
+        }
+    }
+
+    /// A setting of a profile in the `Cargo.toml` of the workspace root, such as `debug` of
+    /// `[profile.test]`, with strings unquoted. Profiles in cargo's config files aren't read.
+    pub fn profile_setting(&self, profile: &str, key: &str) -> Result<Option<String>> {
+        let mut toml = String::new();
+        try!(try!(File::open(self.workspace_root.join("Cargo.toml"))).read_to_string(&mut toml));
+        Ok(toml_value(&toml, &format!("profile.{}.{}", profile, key)))
+    }
+
+    /// The travis-cargo configuration, from `[package.metadata.travis-cargo]` of the root package
+    /// or else `[workspace.metadata.travis-cargo]`.
+    pub fn config(&self) -> Option<&Json> {
+        self.root_package()
+            .and_then(|package| package.metadata.find("travis-cargo"))
+            .or_else(|| self.workspace_metadata.find("travis-cargo"))
+    }
+}
+
+/// The value of a dotted key in a TOML document, for simple values like those of profiles, which
+/// aren't arrays or inline tables.
+fn toml_value(toml: &str, dotted_key: &str) -> Option<String> {
+    let normalize = |key: &str| key.replace(' ', "").replace('"', "").replace('\'', "");
+
+    let mut table = String::new();
+    for line in toml.lines() {
+        let line = without_comment(line).trim();
+        if line.starts_with('[') {
+            table = normalize(line.trim_matches(|c| c == '[' || c == ']'));
+            continue;
+        }
+
+        let mut key_value = line.splitn(2, '=');
+        let (key, value) = match (key_value.next(), key_value.next()) {
+            (Some(key), Some(value)) => (normalize(key), value.trim()),
+            _ => continue,
+        };
+        let key = if table.is_empty() { key } else { format!("{}.{}", table, key) };
+        if key == dotted_key {
+            return Some(value.trim_matches(|c| c == '"' || c == '\'').to_owned());
+        }
+    }
+    None
+}
+
+fn without_comment(line: &str) -> &str {
+    let mut quote = None;
+    for (i, c) in line.char_indices() {
+        match (c, quote) {
+            ('"', None) | ('\'', None) => quote = Some(c),
+            (c, Some(open)) if c == open => quote = None,
+            ('#', None) => return &line[..i],
+            _ => {}
+        }
+    }
+    line
 }
@@ -0,0 +1,550 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::process::Command;
+
+use error::{Error, Result};
+use utils;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Channel {
+    Stable,
+    Beta,
+    Nightly,
+    /// A compiler built from source.
+    Dev,
+}
+
+impl Channel {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            Channel::Stable => "stable",
+            Channel::Beta => "beta",
+            Channel::Nightly => "nightly",
+            Channel::Dev => "dev",
+        }
+    }
+
+    /// Whether unstable features are available, which is also the case for dev builds.
+    pub fn is_nightly(&self) -> bool {
+        *self == Channel::Nightly || *self == Channel::Dev
+    }
+}
+
+impl fmt::Display for Channel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A rustc version such as `1.30.0` or `1.31.0-nightly`.
+///
+/// Versions are compared by their numeric components only, so `1.31.0-nightly` is the same as
+/// `1.31.0`. That way, `>=1.31` also holds for the nightlies and betas that lead up to 1.31.
+#[derive(Debug, Clone)]
+pub struct Version {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+    pub pre: Option<String>,
+}
+
+impl Version {
+    /// Parses a full or partial version, missing components being zero.
+    pub fn parse(s: &str) -> Option<Version> {
+        let (numbers, pre) = match s.find('-') {
+            Some(i) => (&s[..i], Some(s[i + 1..].to_owned())),
+            None => (s, None),
+        };
+
+        let mut components = vec![];
+        for component in numbers.split('.') {
+            match component.parse() {
+                Ok(component) => components.push(component),
+                Err(_) => return None,
+            }
+        }
+        if components.len() > 3 {
+            return None;
+        }
+        components.resize(3, 0);
+
+        Some(Version {
+            major: components[0],
+            minor: components[1],
+            patch: components[2],
+            pre: pre,
+        })
+    }
+
+    fn components(&self) -> (u64, u64, u64) {
+        (self.major, self.minor, self.patch)
+    }
+}
+
+impl PartialEq for Version {
+    fn eq(&self, other: &Version) -> bool {
+        self.components() == other.components()
+    }
+}
+
+impl Eq for Version {}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Version) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Version {
+    fn cmp(&self, other: &Version) -> Ordering {
+        self.components().cmp(&other.components())
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(f, "{}.{}.{}", self.major, self.minor, self.patch));
+        if let Some(ref pre) = self.pre {
+            try!(write!(f, "-{}", pre));
+        }
+        Ok(())
+    }
+}
+
+/// The Rust compiler that cargo will use, as described by `rustc -vV`.
+#[derive(Debug, Clone)]
+pub struct Toolchain {
+    pub channel: Channel,
+    pub version: Version,
+    pub commit_hash: Option<String>,
+    pub commit_date: Option<String>,
+    pub host: String,
+    pub llvm_version: Option<String>,
+}
+
+impl Toolchain {
+    pub fn detect() -> Result<Toolchain> {
+        let output = try!(utils::run_output(Command::new("rustc").arg("-vV")));
+        Toolchain::parse(&output)
+    }
+
+    /// Parses the output of `rustc -vV`, which looks like
+    ///
+    /// ```text
+    /// rustc 1.31.0-nightly (4bd4e4130 2018-10-25)
+    /// binary: rustc
+    /// commit-hash: 4bd4e4130c8e4a5f0a7d4a8f4e8a4c0f3b2a1e1d
+    /// commit-date: 2018-10-25
+    /// host: x86_64-unknown-linux-gnu
+    /// release: 1.31.0-nightly
+    /// LLVM version: 8.0
+    /// ```
+    pub fn parse(verbose_version: &str) -> Result<Toolchain> {
+        let field = |name: &str| {
+            let prefix = format!("{}: ", name);
+            verbose_version.lines()
+                           .find(|line| line.starts_with(&prefix))
+                           .map(|line| line[prefix.len()..].trim().to_owned())
+                           .and_then(|value| if value == "unknown" { None } else { Some(value) })
+        };
+
+        let release = try!(field("release").ok_or_else(|| {
+            Error::ToolchainParse("missing `release`".to_owned())
+        }));
+        let version = try!(Version::parse(&release).ok_or_else(|| {
+            Error::ToolchainParse(format!("invalid release `{}`", release))
+        }));
+
+        let channel = match version.pre {
+            Some(ref pre) if pre.starts_with("nightly") => Channel::Nightly,
+            Some(ref pre) if pre.starts_with("beta") => Channel::Beta,
+            Some(ref pre) if pre.starts_with("dev") => Channel::Dev,
+            _ => Channel::Stable,
+        };
+
+        Ok(Toolchain {
+            channel: channel,
+            version: version,
+            commit_hash: field("commit-hash"),
+            commit_date: field("commit-date"),
+            host: try!(field("host").ok_or_else(|| {
+                Error::ToolchainParse("missing `host`".to_owned())
+            })),
+            llvm_version: field("LLVM version"),
+        })
+    }
+
+    /// Whether the toolchain matches the version given to `--only` or `--skip`. This can be a
+    /// channel (`stable`, `beta`, `nightly` or `dev`, `nightly` also matching dev builds), an
+    /// exact version (`1.30.0` or `=1.30.0`, and `1.30` for any `1.30.x`), or a comparison with a
+    /// version (`>=1.30`, `<1.31.0`, ...).
+    pub fn matches(&self, spec: &str) -> bool {
+        match spec {
+            "nightly" => return self.channel.is_nightly(),
+            "stable" | "beta" | "dev" => return self.channel.as_str() == spec,
+            _ => {}
+        }
+
+        for &(op, ordering) in COMPARISONS {
+            if spec.starts_with(op) {
+                return match Version::parse(spec[op.len()..].trim()) {
+                    Some(version) => ordering.contains(&self.version.cmp(&version)),
+                    None => false,
+                };
+            }
+        }
+
+        // an exact version only constrains the components that it mentions.
+        let spec = spec.trim_start_matches('=').trim();
+        let version = format!("{}.{}.{}",
+                              self.version.major,
+                              self.version.minor,
+                              self.version.patch);
+        version == spec || version.starts_with(&format!("{}.", spec))
+    }
+}
+
+// Longer operators come first, so that `>=` isn't taken for `>`.
+const COMPARISONS: &'static [(&'static str, &'static [Ordering])] =
+    &[(">=", &[Ordering::Greater, Ordering::Equal]),
+      ("<=", &[Ordering::Less, Ordering::Equal]),
+      (">", &[Ordering::Greater]),
+      ("<", &[Ordering::Less])];
+
+const EQUALITY: &'static [(&'static str, &'static [Ordering])] = &[("=", &[Ordering::Equal])];
+
+/// A condition on the toolchain, as given to `--only` or `--skip`.
+///
+/// This is a comma-separated list of alternatives, any of which has to match. Each alternative
+/// can be negated with a leading `!`, and is either something that `Toolchain::matches`
+/// understands, optionally bounded by the date of the compiler's commit (`nightly<2020-01-01`,
+/// or just `>=2019-06-01`), or a rustup toolchain such as `nightly-2020-01-01`, which only
+/// matches the version requested from the CI service verbatim.
+#[derive(Debug, Clone)]
+pub struct VersionPredicate {
+    alternatives: Vec<Alternative>,
+}
+
+#[derive(Debug, Clone)]
+struct Alternative {
+    negated: bool,
+    /// The alternative as given, to compare with the version requested from the CI service.
+    verbatim: String,
+    /// What `Toolchain::matches` is asked, or `None` if only the date is bounded.
+    spec: Option<String>,
+    date_bound: Option<(&'static [Ordering], String)>,
+}
+
+impl VersionPredicate {
+    pub fn parse(s: &str) -> Result<VersionPredicate> {
+        let mut alternatives = vec![];
+        for alternative in s.split(',').map(str::trim).filter(|a| !a.is_empty()) {
+            let negations = alternative.len() - alternative.trim_start_matches('!').len();
+            let verbatim = alternative[negations..].trim();
+
+            let (spec, date_bound) = match try!(split_date_bound(verbatim)) {
+                Some((spec, ordering, date)) => (spec, Some((ordering, date.to_owned()))),
+                None => (verbatim, None),
+            };
+            if !spec.is_empty() && !is_spec(spec) && !is_rustup_toolchain(spec) {
+                return Err(Error::InvalidArgument(format!("invalid version `{}`, expected a \
+                                                           channel, a version, a comparison \
+                                                           with a version or a date bound",
+                                                          spec)));
+            }
+
+            alternatives.push(Alternative {
+                negated: negations % 2 == 1,
+                verbatim: verbatim.to_owned(),
+                spec: if spec.is_empty() { None } else { Some(spec.to_owned()) },
+                date_bound: date_bound,
+            });
+        }
+
+        Ok(VersionPredicate { alternatives: alternatives })
+    }
+
+    pub fn matches(&self, toolchain: &Toolchain, ci_version: Option<&str>) -> bool {
+        self.alternatives
+            .iter()
+            .any(|alternative| alternative.matches(toolchain, ci_version) != alternative.negated)
+    }
+}
+
+impl Alternative {
+    fn matches(&self, toolchain: &Toolchain, ci_version: Option<&str>) -> bool {
+        if ci_version == Some(&self.verbatim[..]) {
+            return true;
+        }
+
+        if let Some((ordering, ref date)) = self.date_bound {
+            let in_bounds = toolchain.commit_date
+                                     .as_ref()
+                                     .map(|commit_date| ordering.contains(&commit_date.cmp(date)))
+                                     .unwrap_or(false);
+            if !in_bounds {
+                return false;
+            }
+        }
+
+        match self.spec {
+            Some(ref spec) => toolchain.matches(spec),
+            None => true,
+        }
+    }
+}
+
+/// Whether `Toolchain::matches` understands the spec.
+fn is_spec(spec: &str) -> bool {
+    if ["stable", "beta", "nightly", "dev"].contains(&spec) {
+        return true;
+    }
+
+    let version = COMPARISONS.iter()
+                             .chain(EQUALITY)
+                             .find(|&&(op, _)| spec.starts_with(op))
+                             .map_or(spec, |&(op, _)| &spec[op.len()..]);
+    Version::parse(version.trim()).is_some()
+}
+
+/// Whether this is a toolchain that rustup installs from a dated channel, like
+/// `nightly-2020-01-01`.
+fn is_rustup_toolchain(spec: &str) -> bool {
+    ["stable-", "beta-", "nightly-"].iter().any(|channel| {
+        spec.starts_with(channel) && is_date(&spec[channel.len()..])
+    })
+}
+
+/// Splits `nightly<2020-01-01` into `nightly`, the orderings that `<` allows and the date. A
+/// bound whose value starts like a date has to be a valid one.
+fn split_date_bound(spec: &str) -> Result<Option<(&str, &'static [Ordering], &str)>> {
+    let start = match spec.find(|c| c == '<' || c == '>' || c == '=') {
+        Some(start) => start,
+        None => return Ok(None),
+    };
+
+    let bound = &spec[start..];
+    for &(op, ordering) in COMPARISONS.iter().chain(EQUALITY) {
+        if !bound.starts_with(op) {
+            continue;
+        }
+
+        let date = bound[op.len()..].trim();
+        if is_date(date) {
+            return Ok(Some((spec[..start].trim(), ordering, date)));
+        }
+        // `2020-1-1`, but not a version like `1.31.0-beta`.
+        let year = date.split('-').next().unwrap();
+        if date.contains('-') && year.len() == 4 && year.chars().all(|c| c.is_digit(10)) {
+            return Err(Error::InvalidArgument(format!("invalid date `{}` in `{}`, expected \
+                                                       YYYY-MM-DD",
+                                                      date,
+                                                      spec)));
+        }
+        break;
+    }
+
+    Ok(None)
+}
+
+/// Whether this is a valid `YYYY-MM-DD` date, which compares correctly as a string.
+fn is_date(s: &str) -> bool {
+    let well_formed = s.len() == 10 &&
+                      s.char_indices()
+                       .all(|(i, c)| if i == 4 || i == 7 { c == '-' } else { c.is_digit(10) });
+    if !well_formed {
+        return false;
+    }
+
+    let month: u32 = s[5..7].parse().unwrap();
+    let day: u32 = s[8..10].parse().unwrap();
+    month >= 1 && month <= 12 && day >= 1 && day <= 31
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STABLE: &'static str = "rustc 1.30.0 (da5f414c2 2018-10-24)
+binary: rustc
+commit-hash: da5f414c2c0bfe5198934493f04c676e2b23ff2e
+commit-date: 2018-10-24
+host: x86_64-unknown-linux-gnu
+release: 1.30.0
+LLVM version: 8.0
+";
+
+    const BETA: &'static str = "rustc 1.31.0-beta.4 (04da282bb 2018-11-01)
+binary: rustc
+commit-hash: 04da282bb1c8d3f1ba5ab9b7b2cd8a1e1ee2ad1e
+commit-date: 2018-11-01
+host: x86_64-apple-darwin
+release: 1.31.0-beta.4
+LLVM version: 8.0
+";
+
+    const NIGHTLY: &'static str = "rustc 1.32.0-nightly (6b9b97bd9 2018-11-15)
+binary: rustc
+commit-hash: 6b9b97bd9b704f85f0184f7a213cc4d62bd9654c
+commit-date: 2018-11-15
+host: x86_64-pc-windows-msvc
+release: 1.32.0-nightly
+LLVM version: 8.0
+";
+
+    // a compiler built from source doesn't know its commit.
+    const DEV: &'static str = "rustc 1.33.0-dev
+binary: rustc
+commit-hash: unknown
+commit-date: unknown
+host: x86_64-unknown-linux-gnu
+release: 1.33.0-dev
+";
+
+    #[test]
+    fn parse_stable() {
+        let toolchain = Toolchain::parse(STABLE).unwrap();
+        assert_eq!(toolchain.channel, Channel::Stable);
+        assert_eq!((toolchain.version.major, toolchain.version.minor, toolchain.version.patch),
+                   (1, 30, 0));
+        assert_eq!(toolchain.version.pre, None);
+        assert_eq!(toolchain.commit_hash,
+                   Some("da5f414c2c0bfe5198934493f04c676e2b23ff2e".to_owned()));
+        assert_eq!(toolchain.commit_date, Some("2018-10-24".to_owned()));
+        assert_eq!(toolchain.host, "x86_64-unknown-linux-gnu");
+        assert_eq!(toolchain.llvm_version, Some("8.0".to_owned()));
+    }
+
+    #[test]
+    fn parse_channels() {
+        let beta = Toolchain::parse(BETA).unwrap();
+        assert_eq!(beta.channel, Channel::Beta);
+        assert_eq!(beta.version.to_string(), "1.31.0-beta.4");
+
+        let nightly = Toolchain::parse(NIGHTLY).unwrap();
+        assert_eq!(nightly.channel, Channel::Nightly);
+        assert_eq!(nightly.version.to_string(), "1.32.0-nightly");
+        assert_eq!(nightly.host, "x86_64-pc-windows-msvc");
+
+        let dev = Toolchain::parse(DEV).unwrap();
+        assert_eq!(dev.channel, Channel::Dev);
+        assert!(dev.channel.is_nightly());
+        assert_eq!(dev.commit_hash, None);
+        assert_eq!(dev.commit_date, None);
+        assert_eq!(dev.llvm_version, None);
+    }
+
+    #[test]
+    fn parse_errors() {
+        match Toolchain::parse("rustc 1.30.0\nhost: x86_64-unknown-linux-gnu\n") {
+            Err(Error::ToolchainParse(message)) => assert!(message.contains("release")),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        match Toolchain::parse("release: one.two\nhost: x86_64-unknown-linux-gnu\n") {
+            Err(Error::ToolchainParse(message)) => assert!(message.contains("one.two")),
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert!(Toolchain::parse("release: 1.30.0\n").is_err());
+    }
+
+    #[test]
+    fn matches_channels() {
+        let stable = Toolchain::parse(STABLE).unwrap();
+        assert!(stable.matches("stable"));
+        assert!(!stable.matches("beta"));
+        assert!(!stable.matches("nightly"));
+
+        // dev builds have unstable features too.
+        let dev = Toolchain::parse(DEV).unwrap();
+        assert!(dev.matches("dev"));
+        assert!(dev.matches("nightly"));
+        assert!(!dev.matches("stable"));
+    }
+
+    #[test]
+    fn matches_versions() {
+        let stable = Toolchain::parse(STABLE).unwrap();
+        assert!(stable.matches("1.30.0"));
+        assert!(stable.matches("=1.30.0"));
+        assert!(stable.matches("1.30"));
+        assert!(stable.matches("1"));
+        assert!(!stable.matches("1.3"));
+        assert!(!stable.matches("1.30.1"));
+
+        assert!(stable.matches(">=1.30"));
+        assert!(stable.matches("<=1.30.0"));
+        assert!(stable.matches("> 1.29.2"));
+        assert!(stable.matches("<1.31.0"));
+        assert!(!stable.matches(">1.30.0"));
+        assert!(!stable.matches("<1.30"));
+    }
+
+    #[test]
+    fn prereleases_match_their_release() {
+        let beta = Toolchain::parse(BETA).unwrap();
+        assert!(beta.matches(">=1.31"));
+        assert!(beta.matches("1.31.0"));
+        assert!(!beta.matches("<1.31"));
+    }
+
+    fn predicate(s: &str, verbose_version: &str, ci_version: Option<&str>) -> bool {
+        VersionPredicate::parse(s).unwrap().matches(&Toolchain::parse(verbose_version).unwrap(),
+                                                    ci_version)
+    }
+
+    #[test]
+    fn predicate_lists() {
+        assert!(predicate("stable,beta", STABLE, None));
+        assert!(predicate("stable, beta", BETA, None));
+        assert!(!predicate("stable,beta", NIGHTLY, None));
+        assert!(predicate("nightly,>=1.30", STABLE, None));
+        assert!(!predicate("nightly,<1.30", STABLE, None));
+        // empty alternatives are ignored, and a predicate without any doesn't match.
+        assert!(predicate(",stable,", STABLE, None));
+        assert!(!predicate("", STABLE, None));
+    }
+
+    #[test]
+    fn predicate_negation() {
+        assert!(!predicate("!stable", STABLE, None));
+        assert!(predicate("!nightly", STABLE, None));
+        assert!(predicate("!!stable", STABLE, None));
+        // an alternative is negated on its own.
+        assert!(predicate("!stable,1.30", STABLE, None));
+        assert!(!predicate("!stable,!1.30", STABLE, None));
+    }
+
+    #[test]
+    fn predicate_date_bounds() {
+        // the nightly was built on 2018-11-15.
+        assert!(predicate("nightly<2018-11-16", NIGHTLY, None));
+        assert!(!predicate("nightly<2018-11-15", NIGHTLY, None));
+        assert!(predicate("nightly<=2018-11-15", NIGHTLY, None));
+        assert!(predicate(">=2018-11-15", NIGHTLY, None));
+        assert!(!predicate(">2018-11-15", NIGHTLY, None));
+        assert!(predicate("=2018-11-15", NIGHTLY, None));
+        assert!(!predicate("stable<2018-11-16", NIGHTLY, None));
+        assert!(predicate("!nightly<2018-11-01", NIGHTLY, None));
+
+        // a compiler without a commit date is outside of every bound.
+        assert!(!predicate(">=2000-01-01", DEV, None));
+    }
+
+    #[test]
+    fn predicate_ci_version() {
+        assert!(predicate("nightly-2018-11-14", NIGHTLY, Some("nightly-2018-11-14")));
+        assert!(!predicate("nightly-2018-11-14", NIGHTLY, Some("nightly")));
+        assert!(!predicate("!nightly-2018-11-14", NIGHTLY, Some("nightly-2018-11-14")));
+    }
+
+    #[test]
+    fn invalid_predicates() {
+        for s in &["nightyl", "stable,nightyl", "!xxstable", ">=one", "nightly<2018-13-01",
+                   ">=2018-11-32", "<2018-1-1"] {
+            match VersionPredicate::parse(s) {
+                Err(Error::InvalidArgument(_)) => {}
+                other => panic!("unexpected result for `{}`: {:?}", s, other),
+            }
+        }
+    }
+}
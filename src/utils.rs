@@ -1,26 +1,125 @@
-use std::process::{self, Command, Stdio};
-use std::str;
+use std::env;
+use std::io::prelude::*;
+use std::path::PathBuf;
+use std::process::{Command, ExitStatus, Stdio};
+use std::thread;
 
-pub fn run(command: &mut Command) {
-    let status = command.status().unwrap();
+use docopt::Docopt;
+use rustc_serialize::Decodable;
 
-    if !status.success() {
-        process::exit(status.code().unwrap());
+use error::{Error, Result};
+
+pub fn run(command: &mut Command) -> Result<()> {
+    let status = try!(command.status());
+    check_status(command, status, None)
+}
+
+pub fn run_output(command: &mut Command) -> Result<String> {
+    let output = try!(command.stderr(Stdio::inherit()).output());
+    if !output.status.success() {
+        print!("{}", String::from_utf8_lossy(&output.stdout));
+        try!(check_status(command, output.status, None));
     }
+
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
 }
 
-pub fn run_output(command: &mut Command) -> String {
-    let output = command.stderr(Stdio::inherit()).output().unwrap();
+/// Like `run_output`, but feeds `input` to the command's stdin. This keeps secrets such as tokens
+/// out of the command line.
+pub fn run_input(command: &mut Command, input: &[u8]) -> Result<String> {
+    let mut child = try!(command.stdin(Stdio::piped())
+                                .stdout(Stdio::piped())
+                                .stderr(Stdio::inherit())
+                                .spawn());
+
+    // Write from another thread, so that neither side blocks on a full pipe.
+    let mut stdin = child.stdin.take().unwrap();
+    let input = input.to_owned();
+    let writer = thread::spawn(move || stdin.write_all(&input));
+
+    let output = try!(child.wait_with_output());
+    // the command may not read all of its input before failing.
+    let written = writer.join().unwrap();
     if !output.status.success() {
-        print!("{}", str::from_utf8(&output.stdout).unwrap());
-        process::exit(output.status.code().unwrap());
+        print!("{}", String::from_utf8_lossy(&output.stdout));
+        try!(check_status(command, output.status, None));
     }
+    try!(written);
 
-    String::from_utf8(output.stdout).unwrap()
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
 }
 
-pub fn run_filter(filter: &str, command: &mut Command) {
-    let replacement = String::from_utf8(vec![b'X'; filter.len()]).unwrap();
+/// Like `run`, but collects the command's stdout and then its stderr into `output`, for commands
+/// that run concurrently with others.
+pub fn run_buffered(command: &mut Command, output: &mut Vec<u8>) -> Result<()> {
+    let collected = try!(command.output());
+    output.extend_from_slice(&collected.stdout);
+    output.extend_from_slice(&collected.stderr);
+    check_status(command, collected.status, None)
+}
+
+pub fn run_filter(filter: &str, command: &mut Command) -> Result<()> {
+    let output = try!(command.stderr(Stdio::inherit()).output());
+    print!("{}", mask(&String::from_utf8_lossy(&output.stdout), filter));
+
+    check_status(command, output.status, Some(filter))
+}
+
+/// Decodes the command line of a subcommand, which starts with the program and the subcommand's
+/// name, like `travis-cargo coverage --verify`. `None` means that `--help` was given, and the
+/// usage has been printed.
+pub fn parse_args<T: Decodable>(usage: &str, argv: &[String]) -> Result<Option<T>> {
+    match Docopt::new(usage).and_then(|dopt| dopt.argv(argv).decode()) {
+        Ok(args) => Ok(Some(args)),
+        Err(ref err) if !err.fatal() => {
+            println!("{}", err);
+            Ok(None)
+        }
+        Err(err) => Err(Error::InvalidArgument(err.to_string())),
+    }
+}
 
-    print!("{}", run_output(command).replace(filter, &replacement));
+/// Reads an environment variable that must be set.
+pub fn env_var(name: &str) -> Result<String> {
+    env::var(name).map_err(|_| Error::MissingEnvVar(name.to_owned()))
+}
+
+/// Runs a git command, returning its trimmed output or `None` if it fails or prints nothing.
+pub fn git(args: &[&str]) -> Option<String> {
+    let output = match Command::new("git").args(args).stderr(Stdio::null()).output() {
+        Ok(output) => output,
+        Err(_) => return None,
+    };
+
+    if !output.status.success() {
+        return None;
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout).trim().to_owned();
+    if stdout.is_empty() { None } else { Some(stdout) }
+}
+
+/// Finds an executable on the `PATH`.
+pub fn find_in_path(name: &str) -> Option<PathBuf> {
+    env::var_os("PATH").and_then(|path| {
+        env::split_paths(&path).map(|dir| dir.join(name)).find(|candidate| candidate.is_file())
+    })
+}
+
+fn check_status(command: &Command, status: ExitStatus, filter: Option<&str>) -> Result<()> {
+    if status.success() {
+        return Ok(());
+    }
+
+    // The command line may contain the secret that the output was filtered for.
+    let command = format!("{:?}", command);
+    Err(Error::CommandFailed {
+        command: filter.map(|filter| mask(&command, filter)).unwrap_or(command),
+        status: status,
+    })
+}
+
+fn mask(text: &str, filter: &str) -> String {
+    let replacement = String::from_utf8(vec![b'X'; filter.len()]).unwrap();
+    text.replace(filter, &replacement)
 }
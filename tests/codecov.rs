@@ -0,0 +1,72 @@
+extern crate travis_cargo;
+
+use std::fs::{self, File};
+use std::path::Path;
+
+use travis_cargo::coverage::codecov::{self, Upload};
+
+mod support;
+
+use support::{CI, FakeCi, upload_report, upload_source_root};
+
+fn value<'a>(query: &'a [(&'static str, String)], key: &str) -> Option<&'a str> {
+    query.iter().find(|&&(k, _)| k == key).map(|&(_, ref value)| &value[..])
+}
+
+#[test]
+fn query() {
+    let upload = Upload {
+        token: Some("secret".to_owned()),
+        flags: vec!["unittests".to_owned(), "linux".to_owned()],
+        name: None,
+    };
+    let ci = FakeCi { pull_request: Some("12"), ..CI };
+    let query = codecov::query(&ci, &upload).unwrap();
+
+    assert_eq!(value(&query, "commit"), Some("0123456789abcdef0123456789abcdef01234567"));
+    assert_eq!(value(&query, "token"), Some("secret"));
+    assert_eq!(value(&query, "service"), Some("travis"));
+    assert_eq!(value(&query, "branch"), Some("master"));
+    assert_eq!(value(&query, "pr"), Some("12"));
+    assert_eq!(value(&query, "slug"), Some("owner/repo"));
+    assert_eq!(value(&query, "build"), Some("7"));
+    assert_eq!(value(&query, "job"), Some("42"));
+    assert_eq!(value(&query, "build_url"), Some("https://ci.example.com/builds/7"));
+    assert_eq!(value(&query, "flags"), Some("unittests,linux"));
+    assert_eq!(value(&query, "name"), None);
+}
+
+#[test]
+fn payload() {
+    let mut payload = vec![];
+    codecov::write_payload(&upload_report(), &mut payload, &upload_source_root()).unwrap();
+    let payload = String::from_utf8(payload).unwrap();
+
+    let expected = format!("src/lib.rs\n<<<<<< network\n# path=lcov.info\nSF:{}\nFNF:0\nFNH:0\n\
+                            DA:1,3\nDA:2,3\nDA:6,1\nDA:7,0\nLF:4\nLH:3\nend_of_record\n<<<<<< EOF\n",
+                           upload_source_root().join("src/lib.rs").display());
+    assert_eq!(payload, expected);
+}
+
+#[test]
+fn upload() {
+    let (url, requests) = support::mock_server(&["http://mock/report\n{endpoint}/storage?sig=1\n",
+                                                 ""]);
+    let query = vec![("commit", "0123".to_owned()), ("token", "a secret&".to_owned())];
+
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("target/codecov-test");
+    fs::create_dir_all(&dir).unwrap();
+    let payload = dir.join("codecov.txt");
+    let mut file = File::create(&payload).unwrap();
+    codecov::write_payload(&upload_report(), &mut file, &upload_source_root()).unwrap();
+    codecov::upload(&url, &query, &payload).unwrap();
+
+    let request = requests.recv().unwrap();
+    assert_eq!(request.request_line,
+               "POST /upload/v4?commit=0123&token=a%20secret%26 HTTP/1.1");
+    assert!(request.headers.iter().any(|header| header == "Accept: text/plain"));
+
+    let request = requests.recv().unwrap();
+    assert_eq!(request.request_line, "PUT /storage?sig=1 HTTP/1.1");
+    assert!(request.body.starts_with("src/lib.rs\n<<<<<< network\n"));
+}
@@ -0,0 +1,75 @@
+extern crate travis_cargo;
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+use travis_cargo::Error;
+use travis_cargo::coverage::patch::{self, FilePatch, PatchCoverage};
+use travis_cargo::coverage::report::Report;
+use travis_cargo::coverage::threshold::Thresholds;
+
+const SOURCE_ROOT: &'static str = "/home/travis/build/euclio/travis-cargo-rust";
+
+fn fixture(name: &str) -> File {
+    File::open(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/report").join(name))
+        .unwrap()
+}
+
+fn changed_lines() -> BTreeMap<PathBuf, Vec<u32>> {
+    let mut diff = String::new();
+    fixture("patch.diff").read_to_string(&mut diff).unwrap();
+    patch::changed_lines(&diff).unwrap()
+}
+
+fn patch_coverage() -> PatchCoverage {
+    let report = Report::from_lcov(BufReader::new(fixture("llvm.lcov"))).unwrap();
+    PatchCoverage::new(&report, Path::new(SOURCE_ROOT), &changed_lines())
+}
+
+#[test]
+fn changed_lines_of_diff() {
+    let changed = changed_lines();
+    assert_eq!(changed.keys().collect::<Vec<_>>(),
+               vec![Path::new("src/lib.rs"), Path::new("tests/foo.rs")]);
+    assert_eq!(changed[Path::new("src/lib.rs")], vec![9, 10, 15]);
+    assert_eq!(changed[Path::new("tests/foo.rs")], vec![1, 2, 3, 4, 5, 6, 7]);
+}
+
+#[test]
+fn patch_coverage_of_report() {
+    let patch = patch_coverage();
+
+    // only the lines with code in them count.
+    assert_eq!(patch.files[Path::new("src/lib.rs")],
+               FilePatch {
+                   covered: vec![9],
+                   uncovered: vec![10, 15],
+               });
+    assert_eq!(patch.files[Path::new("tests/foo.rs")],
+               FilePatch {
+                   covered: vec![5, 6],
+                   uncovered: vec![],
+               });
+    assert_eq!(patch.lines(), (3, 5));
+}
+
+#[test]
+fn patch_threshold() {
+    let report = Report::from_lcov(BufReader::new(fixture("llvm.lcov"))).unwrap();
+    let patch = patch_coverage();
+
+    let thresholds = Thresholds::new(None, None, None, Some(60.0)).unwrap();
+    assert!(thresholds.check(&report, Some(&patch), Path::new(SOURCE_ROOT)).is_ok());
+
+    let thresholds = Thresholds::new(None, None, None, Some(61.0)).unwrap();
+    match thresholds.check(&report, Some(&patch), Path::new(SOURCE_ROOT)) {
+        Err(Error::CoverageBelowMinimum(1)) => {}
+        result => panic!("unexpected result: {:?}", result),
+    }
+
+    // without a patch, there's nothing to check.
+    assert!(thresholds.check(&report, None, Path::new(SOURCE_ROOT)).is_ok());
+}
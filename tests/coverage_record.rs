@@ -0,0 +1,91 @@
+#![cfg(unix)]
+
+extern crate travis_cargo;
+
+mod support;
+
+use std::fs::{self, File};
+use std::io::prelude::*;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+
+use travis_cargo::{Error, Manifest, Toolchain};
+use travis_cargo::coverage;
+
+use support::temp_dir;
+
+fn write(path: &Path, contents: &str) {
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    File::create(path).unwrap().write_all(contents.as_bytes()).unwrap();
+}
+
+/// A crate with a test and more of a manifest, and a kcov that fails every binary but merges the
+/// kcov fixture.
+fn setup(name: &str, manifest: &str) -> (Manifest, String) {
+    let dir = temp_dir(name);
+    write(&dir.join("Cargo.toml"),
+          &format!("[package]\nname = \"failing\"\nversion = \"0.1.0\"\n\n[workspace]\n{}",
+                   manifest));
+    write(&dir.join("src/lib.rs"), "#[test]\nfn fails() {\n    panic!();\n}\n");
+
+    let fixture = Path::new(env!("CARGO_MANIFEST_DIR"))
+                      .join("tests/fixtures/report/kcov-cobertura.xml");
+    let kcov = dir.join("kcov");
+    write(&kcov,
+          &format!("#!/bin/sh\nif [ \"$1\" = --merge ]; then\n    mkdir -p \"$2/kcov-merged\"\n    \
+                    cp '{}' \"$2/kcov-merged/cobertura.xml\"\n    exit 0\nfi\nexit 101\n",
+                   fixture.display()));
+    fs::set_permissions(&kcov, fs::Permissions::from_mode(0o755)).unwrap();
+
+    (Manifest::new(&dir).unwrap(), kcov.to_str().unwrap().to_owned())
+}
+
+/// Runs `travis-cargo coverage` with `args` on the crate of `manifest`.
+fn coverage(manifest: &Manifest, args: &[&str]) -> Result<(), Error> {
+    let mut argv = vec!["travis-cargo".to_owned(), "coverage".to_owned()];
+    argv.extend(args.iter().map(|arg| String::from(*arg)));
+    argv.push("--".to_owned());
+    argv.push("--manifest-path".to_owned());
+    argv.push(manifest.workspace_root().join("Cargo.toml").to_str().unwrap().to_owned());
+    coverage::coverage(manifest, &Toolchain::detect().unwrap(), &argv)
+}
+
+#[test]
+fn failing_tests_still_write_the_report() {
+    let (manifest, kcov) = setup("coverage-record-no-fail-fast", "");
+    let output = manifest.workspace_root().join("lcov.info");
+
+    let result = coverage(&manifest,
+                          &["--kcov", &kcov, "--no-fail-fast", "--format", "lcov", "--output",
+                            output.to_str().unwrap()]);
+    match result {
+        Err(ref err @ Error::CommandFailed { .. }) => assert_eq!(err.exit_code(), 101),
+        other => panic!("unexpected result: {:?}", other),
+    }
+    let mut lcov = String::new();
+    File::open(&output).unwrap().read_to_string(&mut lcov).unwrap();
+    assert!(lcov.contains("SF:"));
+
+    // Without `--no-fail-fast`, the first failure stops before anything is merged.
+    fs::remove_file(&output).unwrap();
+    let result = coverage(&manifest,
+                          &["--kcov", &kcov, "--format", "lcov", "--output",
+                            output.to_str().unwrap()]);
+    assert!(result.is_err());
+    assert!(!output.exists());
+
+    fs::remove_dir_all(manifest.workspace_root()).unwrap();
+}
+
+#[test]
+fn no_test_binaries() {
+    let (manifest, kcov) = setup("coverage-record-no-binaries",
+                                 "\n[lib]\ntest = false\ndoctest = false\n");
+
+    match coverage(&manifest, &["--kcov", &kcov]) {
+        Err(Error::NoTestBinaries) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+
+    fs::remove_dir_all(manifest.workspace_root()).unwrap();
+}
@@ -0,0 +1,114 @@
+extern crate travis_cargo;
+
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+
+use travis_cargo::coverage::report::{Format, Function, Report};
+
+const SOURCE_ROOT: &'static str = "/home/travis/build/euclio/travis-cargo-rust";
+
+fn fixture(name: &str) -> String {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/report").join(name);
+    let mut contents = String::new();
+    File::open(path).unwrap().read_to_string(&mut contents).unwrap();
+    contents
+}
+
+fn kcov_report() -> Report {
+    Report::from_cobertura(&fixture("kcov-cobertura.xml")).unwrap()
+}
+
+fn llvm_report() -> Report {
+    Report::from_lcov(fixture("llvm.lcov").as_bytes()).unwrap()
+}
+
+fn write(report: &Report, format: Format) -> String {
+    let mut out = vec![];
+    report.write(format, &mut out, Path::new(SOURCE_ROOT)).unwrap();
+    let out = String::from_utf8(out).unwrap();
+
+    // the timestamp is the only thing that changes between runs.
+    match out.find(" timestamp=\"") {
+        Some(start) => {
+            let end = start + out[start..].find("\">").unwrap();
+            format!("{}{}", &out[..start], &out[end + 1..])
+        }
+        None => out,
+    }
+}
+
+#[test]
+fn kcov_and_llvm_agree_on_lines() {
+    let kcov = kcov_report();
+    let llvm = llvm_report();
+
+    assert_eq!(kcov.files.keys().collect::<Vec<_>>(),
+               vec![&PathBuf::from(SOURCE_ROOT).join("src/lib.rs"),
+                    &PathBuf::from(SOURCE_ROOT).join("tests/foo.rs")]);
+    for (kcov_file, llvm_file) in kcov.files.values().zip(llvm.files.values()) {
+        assert_eq!(kcov_file.lines, llvm_file.lines);
+    }
+    assert_eq!(kcov.lines(), (5, 7));
+    assert_eq!(llvm.lines(), (5, 7));
+}
+
+#[test]
+fn functions_only_from_llvm() {
+    assert_eq!(kcov_report().functions(), (0, 0));
+    assert_eq!(llvm_report().functions(), (3, 4));
+}
+
+#[test]
+fn lcov_function_names() {
+    let lcov = "SF:src/lib.rs\nFN:3,<Vec<T> as Trait<A, B>>::call\n\
+                FN:9,<Vec<T> as Trait<A, B>>::call\nFNDA:2,<Vec<T> as Trait<A, B>>::call\n\
+                FNDA:0,<Vec<T> as Trait<A, B>>::call\nend_of_record\n";
+    let report = Report::from_lcov(lcov.as_bytes()).unwrap();
+    let name = "<Vec<T> as Trait<A, B>>::call".to_owned();
+    assert_eq!(report.files[Path::new("src/lib.rs")].functions,
+               vec![Function { name: name.clone(), line: 3, hits: 2 },
+                    Function { name: name, line: 9, hits: 0 }]);
+
+    // Functions that share a name keep their own hits through a round trip.
+    let mut written = vec![];
+    report.write_lcov(&mut written).unwrap();
+    assert_eq!(Report::from_lcov(&written[..]).unwrap(), report);
+}
+
+#[test]
+fn lcov() {
+    assert_eq!(write(&llvm_report(), Format::Lcov), fixture("expected.lcov"));
+}
+
+#[test]
+fn lcov_round_trip() {
+    let report = llvm_report();
+    let lcov = write(&report, Format::Lcov);
+    assert_eq!(Report::from_lcov(lcov.as_bytes()).unwrap(), report);
+}
+
+#[test]
+fn cobertura() {
+    assert_eq!(write(&llvm_report(), Format::Cobertura),
+               fixture("expected-cobertura.xml"));
+}
+
+#[test]
+fn cobertura_round_trip() {
+    let report = kcov_report();
+    let cobertura = write(&report, Format::Cobertura);
+    assert_eq!(Report::from_cobertura(&cobertura).unwrap(), report);
+}
+
+#[test]
+fn json_summary() {
+    assert_eq!(write(&kcov_report(), Format::JsonSummary),
+               fixture("expected-summary.json"));
+}
+
+#[test]
+fn unknown_format() {
+    assert!(Format::parse("lcov").is_ok());
+    assert!(Format::parse("html").is_err());
+}
@@ -0,0 +1,68 @@
+extern crate rustc_serialize;
+extern crate travis_cargo;
+
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::Path;
+
+use rustc_serialize::json::Json;
+use travis_cargo::Error;
+use travis_cargo::coverage::report::Report;
+use travis_cargo::coverage::threshold::Thresholds;
+
+const SOURCE_ROOT: &'static str = "/home/travis/build/euclio/travis-cargo-rust";
+
+fn report() -> Report {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/report/llvm.lcov");
+    let mut lcov = String::new();
+    File::open(path).unwrap().read_to_string(&mut lcov).unwrap();
+    Report::from_lcov(lcov.as_bytes()).unwrap()
+}
+
+fn check(config: &str, lines: Option<f64>, functions: Option<f64>) -> Option<usize> {
+    let config = Json::from_str(config).unwrap();
+    let thresholds = Thresholds::new(Some(&config), lines, functions, None).unwrap();
+    match thresholds.check(&report(), None, Path::new(SOURCE_ROOT)) {
+        Ok(()) => None,
+        Err(Error::CoverageBelowMinimum(failures)) => Some(failures),
+        Err(err) => panic!("unexpected error: {}", err),
+    }
+}
+
+#[test]
+fn totals() {
+    // 5 of 7 lines and 3 of 4 functions are covered.
+    assert_eq!(check("{}", Some(71.0), Some(75.0)), None);
+    assert_eq!(check("{}", Some(72.0), None), Some(1));
+    assert_eq!(check("{}", Some(72.0), Some(76.0)), Some(2));
+}
+
+#[test]
+fn flags_override_config() {
+    let config = r#"{"coverage": {"fail-under-lines": 90}}"#;
+    assert_eq!(check(config, None, None), Some(1));
+    assert_eq!(check(config, Some(50.0), None), None);
+}
+
+#[test]
+fn files() {
+    let config = r#"{"coverage": {"files": {"src/lib.rs": 60,
+                                            "tests/foo.rs": {"lines": 100, "functions": 100}}}}"#;
+    assert_eq!(check(config, None, None), None);
+
+    let config = r#"{"coverage": {"files": {"src/lib.rs": {"functions": 70}}}}"#;
+    assert_eq!(check(config, None, None), Some(1));
+}
+
+#[test]
+fn missing_file_is_uncovered() {
+    assert_eq!(check(r#"{"coverage": {"files": {"src/missing.rs": 1}}}"#, None, None),
+               Some(1));
+}
+
+#[test]
+fn invalid() {
+    let config = Json::from_str(r#"{"coverage": {"files": {"src/lib.rs": "most"}}}"#).unwrap();
+    assert!(Thresholds::new(Some(&config), None, None, None).is_err());
+    assert!(Thresholds::new(None, Some(101.0), None, None).is_err());
+}
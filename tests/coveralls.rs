@@ -0,0 +1,77 @@
+extern crate rustc_serialize;
+extern crate travis_cargo;
+
+use rustc_serialize::json::Json;
+use travis_cargo::Error;
+use travis_cargo::coverage::coveralls;
+
+mod support;
+
+use support::{CI, FakeCi, upload_report, upload_source_root};
+
+#[test]
+fn job() {
+    let job = coveralls::job(&upload_report(), &CI, &upload_source_root(), Some("secret"), true)
+                  .unwrap();
+
+    assert_eq!(job.find("service_name").and_then(Json::as_string), Some("travis-ci"));
+    assert_eq!(job.find("service_job_id").and_then(Json::as_string), Some("42"));
+    assert_eq!(job.find("service_number").and_then(Json::as_string), Some("7"));
+    assert_eq!(job.find("repo_token").and_then(Json::as_string), Some("secret"));
+    assert_eq!(job.find("parallel").and_then(Json::as_boolean), Some(true));
+    assert!(job.find("service_pull_request").is_none());
+
+    let source_files = job.find("source_files").and_then(Json::as_array).unwrap();
+    assert_eq!(source_files.len(), 1);
+    let file = &source_files[0];
+    assert_eq!(file.find("name").and_then(Json::as_string), Some("src/lib.rs"));
+    assert_eq!(file.find("source_digest").and_then(Json::as_string),
+               Some("b07b69ce1b67432370cb7d35a888156f"));
+    assert_eq!(file.find("coverage").unwrap().to_string(),
+               "[3,3,null,null,null,1,0,null]");
+}
+
+#[test]
+fn job_without_token() {
+    let job = coveralls::job(&upload_report(), &CI, &upload_source_root(), None, false)
+                  .unwrap();
+    assert!(job.find("repo_token").is_none());
+    assert!(job.find("parallel").is_none());
+}
+
+#[test]
+fn upload() {
+    let (endpoint, requests) =
+        support::mock_server(&[r#"{"message":"Job #7.1","url":"http://mock/jobs/1"}"#]);
+    let job = coveralls::job(&upload_report(), &CI, &upload_source_root(), Some("secret"), false)
+                  .unwrap();
+    coveralls::upload(&endpoint, &job).unwrap();
+
+    let request = requests.recv().unwrap();
+    assert_eq!(request.request_line, "POST /api/v1/jobs HTTP/1.1");
+    assert!(request.body.contains(r#"name="json_file"; filename="coveralls.json""#));
+    assert!(request.body.contains(&job.to_string()));
+}
+
+#[test]
+fn finish() {
+    let (endpoint, requests) = support::mock_server(&[r#"{"done":true}"#]);
+    coveralls::finish(&format!("{}/", endpoint), &CI, Some("secret")).unwrap();
+
+    let request = requests.recv().unwrap();
+    assert_eq!(request.request_line, "POST /webhook HTTP/1.1");
+    let body = Json::from_str(&request.body).unwrap();
+    assert_eq!(body.find("repo_token").and_then(Json::as_string), Some("secret"));
+    assert_eq!(body.find_path(&["payload", "build_num"]).and_then(Json::as_string),
+               Some("7"));
+    assert_eq!(body.find_path(&["payload", "status"]).and_then(Json::as_string),
+               Some("done"));
+}
+
+#[test]
+fn finish_needs_build_number() {
+    match coveralls::finish("http://127.0.0.1:1", &FakeCi { build_number: None, ..CI }, None) {
+        Err(Error::MissingCiInfo("build number")) => {}
+        result => panic!("unexpected result: {:?}", result),
+    }
+}
@@ -0,0 +1,36 @@
+extern crate rustc_serialize;
+extern crate travis_cargo;
+
+use rustc_serialize::json::Json;
+use travis_cargo::Error;
+use travis_cargo::doc_upload::config::Options;
+
+#[test]
+fn command_line_takes_precedence() {
+    let config = Json::from_str(r#"{"doc-upload": {"target-branch": "pages",
+                                                   "target-dir": "docs",
+                                                   "host": "gitlab.com"}}"#)
+                     .unwrap();
+    let options = Options {
+        target_branch: Some("main".to_owned()),
+        ..Options::default()
+    };
+
+    assert_eq!(options.with_config(Some(&config)).unwrap(),
+               Options {
+                   target_branch: Some("main".to_owned()),
+                   target_dir: Some("docs".to_owned()),
+                   host: Some("gitlab.com".to_owned()),
+                   ..Options::default()
+               });
+    assert_eq!(Options::default().with_config(None).unwrap(), Options::default());
+}
+
+#[test]
+fn values_must_be_strings() {
+    let config = Json::from_str(r#"{"doc-upload": {"remote": 1}}"#).unwrap();
+    match Options::default().with_config(Some(&config)) {
+        Err(Error::ManifestParse(message)) => assert!(message.contains("doc-upload.remote")),
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
@@ -0,0 +1,36 @@
+extern crate travis_cargo;
+
+use std::path::Path;
+
+use travis_cargo::Manifest;
+use travis_cargo::doc_upload::landing;
+
+#[test]
+fn single_library_redirects() {
+    let manifest = Manifest::new(env!("CARGO_MANIFEST_DIR")).unwrap();
+    assert_eq!(landing::index_html(&manifest),
+               "<meta http-equiv=refresh content=0;url=travis_cargo/index.html>\n");
+}
+
+#[test]
+fn workspace_lists_every_crate() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/workspace");
+    let html = landing::index_html(&Manifest::new(dir).unwrap());
+
+    assert!(html.contains("<title>workspace</title>"));
+    assert!(html.contains("<li><a href=\"parser/index.html\">parser</a> 1.2.0 &mdash; Parses \
+                           &lt;things&gt; &amp; stuff</li>"));
+    assert!(html.contains("<li><a href=\"parser_derive/index.html\">parser-derive</a> \
+                           0.3.0-beta.1</li>"));
+
+    // The binary-only package isn't documented by cargo, so its README is shown instead.
+    assert!(!html.contains("tool/index.html"));
+    assert!(html.contains("<h2>tool 0.1.0</h2>\n<p>A command-line tool</p>\n\
+                           <h3>tool</h3>\n\
+                           <p>Run <code>tool &lt;file&gt;</code>, which\nprints its summary.</p>\n\
+                           <h4>Options</h4>\n\
+                           <ul>\n<li><code>--quiet</code>: print nothing</li>\n\
+                           <li><code>--json</code>: print JSON</li>\n</ul>\n\
+                           <pre><code>tool --json Cargo.toml &gt; summary.json\n</code></pre>\n\
+                           </body>"));
+}
@@ -0,0 +1,161 @@
+extern crate travis_cargo;
+
+mod support;
+
+use std::fs::{self, File};
+use std::io::prelude::*;
+use std::path::Path;
+
+use travis_cargo::doc_upload::auth::Auth;
+use travis_cargo::doc_upload::publish::{self, Content, Target};
+
+use support::{git, temp_dir};
+
+fn write(path: &Path, contents: &str) {
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    File::create(path).unwrap().write_all(contents.as_bytes()).unwrap();
+}
+
+/// A bare remote, a repository to publish from, and a directory of docs.
+fn setup(name: &str) -> (String, String, String) {
+    let dir = temp_dir(name);
+    let remote = dir.join("remote.git");
+    let repo = dir.join("repo");
+    let docs = dir.join("docs");
+    git(&dir, &["init", "--quiet", "--bare", remote.to_str().unwrap()]);
+    git(&dir, &["init", "--quiet", repo.to_str().unwrap()]);
+    write(&repo.join("README.md"), "not published\n");
+    write(&docs.join("index.html"), "<meta http-equiv=refresh content=0;url=foo/index.html>\n");
+    write(&docs.join("foo/index.html"), "foo\n");
+    write(&docs.join("foo/sub/bar.html"), "bar\n");
+
+    (remote.to_str().unwrap().to_owned(),
+     repo.to_str().unwrap().to_owned(),
+     docs.to_str().unwrap().to_owned())
+}
+
+fn publish(repo: &str, remote: &str, changes: &[(&str, Content)], message: &str) -> Option<String> {
+    let target = Target {
+        remote: remote,
+        branch: "gh-pages",
+        auth: Auth::None,
+    };
+    let repo = Path::new(repo);
+    let parent = publish::fetch(repo, &target).unwrap();
+    publish::publish(repo, &target, parent.as_ref().map(|p| &p[..]), changes, message).unwrap()
+}
+
+#[test]
+fn publishes_to_a_bare_repository() {
+    let (remote, repo, docs) = setup("doc-publish");
+    let (remote_dir, docs_dir) = (Path::new(&remote), Path::new(&docs));
+
+    let first = publish(&repo, &remote, &[("", Content::Dir(docs_dir))], "First").unwrap();
+    assert_eq!(git(remote_dir, &["rev-parse", "gh-pages"]), first);
+    assert_eq!(git(remote_dir, &["ls-tree", "-r", "--name-only", "gh-pages"]),
+               "foo/index.html\nfoo/sub/bar.html\nindex.html");
+    assert_eq!(git(remote_dir, &["show", "gh-pages:foo/sub/bar.html"]), "bar");
+    assert_eq!(git(remote_dir, &["log", "--format=%s", "gh-pages"]), "First");
+
+    // The working tree and index of the repository are left alone.
+    assert_eq!(git(Path::new(&repo), &["status", "--porcelain"]), "?? README.md");
+
+    // Nothing is committed if nothing changed.
+    assert_eq!(publish(&repo, &remote, &[("", Content::Dir(docs_dir))], "Unchanged"),
+               None);
+
+    fs::remove_file(docs_dir.join("foo/sub/bar.html")).unwrap();
+    write(&docs_dir.join("foo/index.html"), "foo 2\n");
+    let second = publish(&repo, &remote, &[("", Content::Dir(docs_dir))], "Second").unwrap();
+    assert_eq!(git(remote_dir, &["rev-parse", "gh-pages^"]), first);
+    assert_eq!(git(remote_dir, &["rev-parse", "gh-pages"]), second);
+    assert_eq!(git(remote_dir, &["ls-tree", "-r", "--name-only", "gh-pages"]),
+               "foo/index.html\nindex.html");
+    assert_eq!(git(remote_dir, &["show", "gh-pages:foo/index.html"]), "foo 2");
+
+    fs::remove_dir_all(docs_dir.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn replaces_only_the_given_paths() {
+    let (remote, repo, docs) = setup("doc-publish-paths");
+    let (remote_dir, docs_dir) = (Path::new(&remote), Path::new(&docs));
+    // Publishing from a subdirectory of the repository works the same.
+    let subdir = Path::new(&repo).join("sub");
+    fs::create_dir(&subdir).unwrap();
+    let repo = subdir.to_str().unwrap();
+
+    publish(repo,
+            &remote,
+            &[("1.0", Content::Dir(docs_dir)), ("index.html", Content::File(b"1.0".to_vec()))],
+            "1.0");
+    fs::remove_file(docs_dir.join("foo/sub/bar.html")).unwrap();
+    publish(repo,
+            &remote,
+            &[("1.1", Content::Dir(docs_dir)), ("index.html", Content::File(b"1.1".to_vec()))],
+            "1.1");
+    assert_eq!(git(remote_dir, &["ls-tree", "-r", "--name-only", "gh-pages"]),
+               "1.0/foo/index.html\n1.0/foo/sub/bar.html\n1.0/index.html\n1.1/foo/index.html\n\
+                1.1/index.html\nindex.html");
+    assert_eq!(git(remote_dir, &["show", "gh-pages:index.html"]), "1.1");
+
+    let head = git(remote_dir, &["rev-parse", "gh-pages"]);
+    git(Path::new(repo), &["fetch", "--quiet", &remote, "gh-pages"]);
+    assert_eq!(publish::dirs(Path::new(repo), &head, "").unwrap(), vec!["1.0", "1.1"]);
+    assert_eq!(publish::dirs(Path::new(repo), &head, "1.0").unwrap(), vec!["foo"]);
+    assert!(publish::dirs(Path::new(repo), &head, "2.0").unwrap().is_empty());
+    assert_eq!(publish::file(Path::new(repo), &head, "index.html").unwrap(),
+               Some("1.1".to_owned()));
+    assert_eq!(publish::file(Path::new(repo), &head, "1.0/missing.html").unwrap(), None);
+
+    fs::remove_dir_all(docs_dir.parent().unwrap()).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn keeps_file_modes_and_symlinks() {
+    use std::os::unix::fs::{PermissionsExt, symlink};
+
+    let (remote, repo, docs) = setup("doc-publish-modes");
+    let (remote_dir, docs_dir) = (Path::new(&remote), Path::new(&docs));
+    write(&docs_dir.join("run.sh"), "#!/bin/sh\n");
+    fs::set_permissions(docs_dir.join("run.sh"), fs::Permissions::from_mode(0o755)).unwrap();
+    symlink("foo/index.html", docs_dir.join("latest.html")).unwrap();
+    symlink("foo", docs_dir.join("latest")).unwrap();
+
+    publish(&repo, &remote, &[("", Content::Dir(docs_dir))], "Modes");
+    let listing = git(remote_dir, &["ls-tree", "-r", "gh-pages"]);
+    let modes: Vec<_> = listing.lines()
+                               .map(|line| {
+                                   let path = line.split('\t').nth(1).unwrap();
+                                   (&line[..6], path)
+                               })
+                               .collect();
+    assert_eq!(modes,
+               vec![("100644", "foo/index.html"),
+                    ("100644", "foo/sub/bar.html"),
+                    ("100644", "index.html"),
+                    ("120000", "latest"),
+                    ("120000", "latest.html"),
+                    ("100755", "run.sh")]);
+    assert_eq!(git(remote_dir, &["show", "gh-pages:latest.html"]), "foo/index.html");
+
+    fs::remove_dir_all(docs_dir.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn fetch_errors_other_than_a_missing_branch() {
+    let (remote, repo, docs) = setup("doc-publish-fetch");
+    let target = Target {
+        remote: &remote,
+        branch: "gh-pages",
+        auth: Auth::None,
+    };
+    assert_eq!(publish::fetch(Path::new(&repo), &target).unwrap(), None);
+
+    let missing = Path::new(&remote).with_file_name("missing.git");
+    let target = Target { remote: missing.to_str().unwrap(), ..target };
+    assert!(publish::fetch(Path::new(&repo), &target).is_err());
+
+    fs::remove_dir_all(Path::new(&docs).parent().unwrap()).unwrap();
+}
@@ -0,0 +1,49 @@
+extern crate travis_cargo;
+
+use travis_cargo::doc_upload::versions;
+
+fn sorted(versions: &[&str]) -> Vec<String> {
+    let mut versions = versions.iter().map(|version| version.to_string()).collect();
+    versions::sort(&mut versions);
+    versions
+}
+
+#[test]
+fn sort() {
+    assert_eq!(sorted(&["v1.9.0", "latest", "v1.10.0", "master", "v0.1.0", "v1.10.0"]),
+               vec!["latest", "v1.10.0", "v1.9.0", "v0.1.0", "master"]);
+    assert_eq!(sorted(&["0.2.0-beta.2", "0.2.0-beta.10", "0.2.0", "0.1.0", "0.2.0-alpha"]),
+               vec!["0.2.0", "0.2.0-beta.10", "0.2.0-beta.2", "0.2.0-alpha", "0.1.0"]);
+    assert_eq!(sorted(&["v1.0.0-rc.1", "v1.0.0", "v1.0.1"]),
+               vec!["v1.0.1", "v1.0.0", "v1.0.0-rc.1"]);
+}
+
+#[test]
+fn dir_name() {
+    assert_eq!(versions::dir_name("v1.0.0"), "v1.0.0");
+    assert_eq!(versions::dir_name("release/1.0"), "release-1.0");
+    assert_eq!(versions::dir_name("feature/docs/draft"), "feature-docs-draft");
+}
+
+#[test]
+fn versions_json() {
+    assert_eq!(versions::versions_json(&sorted(&["1.0.0", "latest"])),
+               r#"["latest","1.0.0"]"#);
+}
+
+#[test]
+fn parse_versions_json() {
+    assert_eq!(versions::parse_versions_json(r#"["latest","1.0.0"]"#),
+               vec!["latest", "1.0.0"]);
+    assert!(versions::parse_versions_json("{}").is_empty());
+    assert!(versions::parse_versions_json("not json").is_empty());
+}
+
+#[test]
+fn index_html() {
+    let html = versions::index_html("foo<bar>", &sorted(&["1.0.0", "latest"]));
+    assert!(html.contains("<title>foo&lt;bar&gt;</title>"));
+    let latest = html.find(r#"<li><a href="latest/index.html">latest</a></li>"#).unwrap();
+    let release = html.find(r#"<li><a href="1.0.0/index.html">1.0.0</a></li>"#).unwrap();
+    assert!(latest < release);
+}
@@ -0,0 +1,44 @@
+extern crate travis_cargo;
+
+use std::path::Path;
+
+use travis_cargo::Manifest;
+
+#[test]
+fn test_env_matches_cargo() {
+    let manifest = Manifest::new(env!("CARGO_MANIFEST_DIR")).unwrap();
+    let package = manifest.root_package().unwrap();
+    let test_env = package.test_env();
+    let var = |name: &str| {
+        test_env.iter()
+                .find(|&&(key, _)| key == name)
+                .map(|&(_, ref value)| value.clone())
+                .unwrap()
+    };
+
+    // cargo set these when it ran this test.
+    for name in &["CARGO_PKG_NAME",
+                  "CARGO_PKG_VERSION",
+                  "CARGO_PKG_VERSION_MAJOR",
+                  "CARGO_PKG_VERSION_MINOR",
+                  "CARGO_PKG_VERSION_PATCH",
+                  "CARGO_PKG_VERSION_PRE",
+                  "CARGO_PKG_AUTHORS",
+                  "CARGO_PKG_DESCRIPTION"] {
+        assert_eq!(var(name), std::env::var(name).unwrap(), "{}", name);
+    }
+    assert_eq!(Path::new(&var("CARGO_MANIFEST_DIR")),
+               Path::new(env!("CARGO_MANIFEST_DIR")));
+    assert_eq!(package.manifest_dir(), Path::new(env!("CARGO_MANIFEST_DIR")));
+}
+
+#[test]
+fn profile_settings() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/workspace");
+    let manifest = Manifest::new(dir).unwrap();
+
+    assert_eq!(manifest.profile_setting("dev", "debug").unwrap(), Some("0".to_owned()));
+    assert_eq!(manifest.profile_setting("test", "debug").unwrap(),
+               Some("line-tables-only".to_owned()));
+    assert_eq!(manifest.profile_setting("release", "debug").unwrap(), None);
+}
@@ -0,0 +1,163 @@
+#![allow(dead_code)]
+
+use std::env;
+use std::fs;
+use std::io::prelude::*;
+use std::io::BufReader;
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+use std::sync::mpsc;
+use std::thread;
+
+use travis_cargo::CiEnvironment;
+use travis_cargo::coverage::report::{FileCoverage, Report};
+
+pub struct FakeCi {
+    pub build_number: Option<&'static str>,
+    pub pull_request: Option<&'static str>,
+}
+
+pub const CI: FakeCi = FakeCi {
+    build_number: Some("7"),
+    pull_request: None,
+};
+
+impl CiEnvironment for FakeCi {
+    fn name(&self) -> &'static str {
+        "travis-ci"
+    }
+
+    fn branch(&self) -> Option<String> {
+        Some("master".to_owned())
+    }
+
+    fn repo_slug(&self) -> Option<String> {
+        Some("owner/repo".to_owned())
+    }
+
+    fn pull_request(&self) -> Option<String> {
+        self.pull_request.map(String::from)
+    }
+
+    fn job_id(&self) -> Option<String> {
+        Some("42".to_owned())
+    }
+
+    fn build_number(&self) -> Option<String> {
+        self.build_number.map(String::from)
+    }
+
+    fn build_url(&self) -> Option<String> {
+        Some("https://ci.example.com/builds/7".to_owned())
+    }
+
+    fn commit(&self) -> Option<String> {
+        Some("0123456789abcdef0123456789abcdef01234567".to_owned())
+    }
+
+    fn tag(&self) -> Option<String> {
+        None
+    }
+
+    fn is_secure_env_available(&self) -> bool {
+        true
+    }
+}
+
+/// The sources that `upload_report` covers, in `tests/fixtures/upload`.
+pub fn upload_source_root() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/upload")
+}
+
+/// A report of `src/lib.rs` under `upload_source_root`, with one uncovered line.
+pub fn upload_report() -> Report {
+    let mut coverage = FileCoverage::default();
+    for &(line, hits) in &[(1, 3), (2, 3), (6, 1), (7, 0)] {
+        coverage.lines.insert(line, hits);
+    }
+
+    let mut report = Report::default();
+    report.files.insert(upload_source_root().join("src/lib.rs"), coverage);
+    report
+}
+
+pub struct Request {
+    /// e.g. `POST /api/v1/jobs HTTP/1.1`
+    pub request_line: String,
+    pub headers: Vec<String>,
+    pub body: String,
+}
+
+/// Serves one request for each response, in order, and sends the requests back. `{endpoint}` in
+/// a response is replaced with the server's address.
+pub fn mock_server(responses: &[&str]) -> (String, mpsc::Receiver<Request>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let endpoint = format!("http://{}", listener.local_addr().unwrap());
+    let responses: Vec<_> = responses.iter()
+                                     .map(|response| response.replace("{endpoint}", &endpoint))
+                                     .collect();
+
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        for response in responses {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            let mut headers = vec![];
+            let mut content_length = 0;
+            loop {
+                let mut header = String::new();
+                reader.read_line(&mut header).unwrap();
+                let header = header.trim().to_owned();
+                let lowercase = header.to_lowercase();
+                if header.is_empty() {
+                    break;
+                } else if lowercase.starts_with("content-length:") {
+                    content_length = header["content-length:".len()..].trim().parse().unwrap();
+                } else if lowercase == "expect: 100-continue" {
+                    (&stream).write_all(b"HTTP/1.1 100 Continue\r\n\r\n").unwrap();
+                }
+                headers.push(header);
+            }
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body).unwrap();
+
+            write!(&stream,
+                   "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                   response.len(),
+                   response)
+                .unwrap();
+            sender.send(Request {
+                      request_line: request_line.trim().to_owned(),
+                      headers: headers,
+                      body: String::from_utf8(body).unwrap(),
+                  })
+                  .unwrap();
+        }
+    });
+
+    (endpoint, receiver)
+}
+
+/// An empty directory of the test's own under the system's temporary directory.
+pub fn temp_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("travis-cargo-{}-{}", name, process::id()));
+    if dir.exists() {
+        fs::remove_dir_all(&dir).unwrap();
+    }
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Runs git in `dir`, panicking if it fails, and returns its trimmed output.
+pub fn git(dir: &Path, args: &[&str]) -> String {
+    let output = Command::new("git").arg("-C").arg(dir).args(args).output().unwrap();
+    assert!(output.status.success(),
+            "git {:?} failed: {}",
+            args,
+            String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap().trim().to_owned()
+}